@@ -0,0 +1,127 @@
+//! The folders asset references in levels are relative to.
+//!
+//! The default root is the `assets` folder Bevy loads from, references into it are plain
+//! relative paths like `models/door.glb`. Extra roots are read from an `asset_roots.ron`
+//! next to the `assets` folder and registered as Bevy asset sources, so references into
+//! them look like `shared://models/door.glb`:
+//!
+//! ```ron
+//! (
+//!     extra_roots: [
+//!         (name: "shared", path: "../shared_assets"),
+//!     ],
+//! )
+//! ```
+//!
+//! Relative root paths are relative to the folder the file is in. The editor and the game
+//! each have their own file, the root names have to match for levels to work in both.
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+pub const ASSET_ROOTS_FILE: &str = "asset_roots.ron";
+pub const DEFAULT_ASSET_ROOT: &str = "assets";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetRoot {
+    // Used as the asset source name, e.g. "shared" for "shared://models/door.glb"
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AssetRoots {
+    // The folder `assets` and relative root paths are in, not part of the file
+    #[serde(skip)]
+    pub base_path: PathBuf,
+    #[serde(default)]
+    pub extra_roots: Vec<AssetRoot>,
+}
+
+impl AssetRoots {
+    /// Read `asset_roots.ron` from `base_path`, only the default root is used if there's none
+    pub fn load(base_path: &Path) -> AssetRoots {
+        let config_path = base_path.join(ASSET_ROOTS_FILE);
+        let mut roots = match fs::read_to_string(&config_path) {
+            Ok(config) => ron::from_str(&config).unwrap_or_else(|e| {
+                eprintln!("Error reading {}: {}", config_path.display(), e);
+                AssetRoots::default()
+            }),
+            Err(_) => AssetRoots::default(),
+        };
+        roots.base_path = base_path.to_path_buf();
+        roots
+    }
+    pub fn default_root_path(&self) -> PathBuf {
+        self.base_path.join(DEFAULT_ASSET_ROOT)
+    }
+    pub fn root_path(&self, root: &AssetRoot) -> PathBuf {
+        self.base_path.join(&root.path)
+    }
+    /// The reference to store for a file on disk, None if it isn't inside any root.
+    /// When roots are nested the innermost one wins
+    pub fn asset_path(&self, file: &Path) -> Option<String> {
+        let file = normalize(file);
+        let default_root = (None, normalize(&self.default_root_path()));
+        let extra_roots = self
+            .extra_roots
+            .iter()
+            .map(|root| (Some(root.name.as_str()), normalize(&self.root_path(root))));
+        let (name, relative_path) = std::iter::once(default_root)
+            .chain(extra_roots)
+            .filter_map(|(name, root_path)| {
+                let relative_path = file.strip_prefix(&root_path).ok()?;
+                Some((
+                    name,
+                    root_path.components().count(),
+                    relative_path.to_path_buf(),
+                ))
+            })
+            .max_by_key(|(_, depth, _)| *depth)
+            .map(|(name, _, relative_path)| (name, relative_path))?;
+        let relative_path = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if relative_path.is_empty() {
+            return None;
+        }
+        Some(match name {
+            Some(name) => format!("{}://{}", name, relative_path),
+            None => relative_path,
+        })
+    }
+    /// Where a stored reference points to on disk, None for an unknown root name
+    pub fn file_path(&self, asset_path: &str) -> Option<PathBuf> {
+        match asset_path.split_once("://") {
+            Some((name, relative_path)) => self
+                .extra_roots
+                .iter()
+                .find(|root| root.name == name)
+                .map(|root| self.root_path(root).join(relative_path)),
+            None => Some(self.default_root_path().join(asset_path)),
+        }
+    }
+}
+// Absolute, with symlinks resolved when the path exists, so the same folder always compares equal
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,791 @@
+//! The level format shared by the editor and the game.
+//!
+//! A level is a RON file holding a [`LevelFile`]:
+//!
+//! ```ron
+//! (
+//!     version: 4,
+//!     entities: [
+//!         (
+//!             id: 1,
+//!             name: Some("Door"),
+//!             parent: None,
+//!             transform: (
+//!                 translation: (0.0, 0.0, -4.0),
+//!                 rotation: (0.0, 0.0, 0.0, 1.0),
+//!                 scale: (1.0, 1.0, 1.0),
+//!             ),
+//!             asset: Some((path: "models/door.glb", label: None)),
+//!             components: [Interactable((kind: Door))],
+//!         ),
+//!         (
+//!             id: 2,
+//!             transform: (
+//!                 translation: (6.0, 0.0, -4.0),
+//!                 rotation: (0.0, 0.0, 0.0, 1.0),
+//!                 scale: (1.0, 1.0, 1.0),
+//!             ),
+//!             prefab: Some((
+//!                 path: "prefabs/door_with_frame.ron",
+//!                 overrides: [(target: 0, name: Some("Back door"))],
+//!             )),
+//!         ),
+//!         (
+//!             id: 3,
+//!             name: Some("Harbour"),
+//!             sub_level: Some((path: "levels/harbour.ron", load_distance: Some(150.0))),
+//!         ),
+//!     ],
+//! )
+//! ```
+//!
+//! - `id` is only meaningful inside the file, `parent` refers to another entity's `id`.
+//!   Parents are listed before their children.
+//! - `transform` is relative to the parent, or to the world for top level entities.
+//! - `asset` paths are relative to an asset root, see [`crate::asset_roots`].
+//! - `prefab` makes the entity an instance of a prefab, see [`PrefabInstance`].
+//! - `sub_level` places another level file's entities below the entity, see [`SubLevelRef`].
+//!
+//! Older files are upgraded by [`LevelFile::from_ron`]:
+//!
+//! - Version 0 is the list of glTF instances with absolute paths the editor used to read,
+//!   the paths are turned into references relative to the [`AssetRoots`].
+//! - Version 1 is the Bevy `DynamicScene` the editor used to save.
+//! - Version 2 is this format without prefabs.
+//! - Version 3 is this format without sub-levels.
+//!
+//! Versions 0 and 1 have no `version` field, every later version does. Bump
+//! [`CURRENT_LEVEL_VERSION`] whenever the format changes and add a migration for the old one.
+use std::{collections::HashMap, fmt};
+
+use ron::{Value, value::Map};
+use serde::{Deserialize, Serialize};
+
+use crate::{Interactable, Player, asset_roots::AssetRoots};
+
+pub const CURRENT_LEVEL_VERSION: u32 = 4;
+// Prefabs inside prefabs deeper than this aren't instantiated, it's most likely a cycle
+const MAX_PREFAB_NESTING: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelFile {
+    pub version: u32,
+    #[serde(default)]
+    pub entities: Vec<LevelEntity>,
+}
+impl Default for LevelFile {
+    fn default() -> Self {
+        LevelFile {
+            version: CURRENT_LEVEL_VERSION,
+            entities: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelEntity {
+    pub id: u64,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub parent: Option<u64>,
+    #[serde(default)]
+    pub transform: LevelTransform,
+    #[serde(default)]
+    pub asset: Option<AssetRef>,
+    #[serde(default)]
+    pub components: Vec<LevelComponent>,
+    #[serde(default)]
+    pub prefab: Option<PrefabInstance>,
+    #[serde(default)]
+    pub sub_level: Option<SubLevelRef>,
+}
+
+/// Plain arrays so the editor and the game don't have to agree on a glam version
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LevelTransform {
+    pub translation: [f32; 3],
+    // Quaternion as x, y, z, w
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+impl Default for LevelTransform {
+    fn default() -> Self {
+        LevelTransform {
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+/// A glTF file (or another scene asset) and optionally which scene, node or mesh inside it,
+/// e.g. "Scene0", "Node3" or "Mesh1"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetRef {
+    pub path: String,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+/// The parts of a glTF an [`AssetRef`] label can point at, the editor and the game spawn nodes
+/// and meshes differently from scenes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetLabel {
+    Scene(usize),
+    Node(usize),
+    Mesh(usize),
+}
+impl AssetLabel {
+    /// Parse a label like "Scene1", "Node3" or "Mesh0", None for labels of anything else
+    pub fn parse(label: &str) -> Option<AssetLabel> {
+        let index = |prefix: &str| label.strip_prefix(prefix)?.parse::<usize>().ok();
+        index("Scene")
+            .map(AssetLabel::Scene)
+            .or_else(|| index("Node").map(AssetLabel::Node))
+            .or_else(|| index("Mesh").map(AssetLabel::Mesh))
+    }
+}
+
+/// Makes an entity an instance of a prefab, a level file with a single root entity.
+///
+/// The instance takes the place of the prefab's root: its `transform` and `parent` place the
+/// prefab, while the name, asset and components of every prefab entity come from the prefab
+/// file with `overrides` applied. The prefab's entities aren't stored in the level, so changes
+/// to the prefab show up in every instance that doesn't override them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrefabInstance {
+    pub path: String,
+    #[serde(default)]
+    pub overrides: Vec<PrefabOverride>,
+}
+/// The properties of one prefab entity that are different in an instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrefabOverride {
+    // Id of the entity in the prefab file
+    pub target: u64,
+    #[serde(default)]
+    pub name: Option<String>,
+    // Ignored for the root, the instance's own transform is used
+    #[serde(default)]
+    pub transform: Option<LevelTransform>,
+    #[serde(default)]
+    pub components: Option<Vec<LevelComponent>>,
+}
+impl PrefabOverride {
+    /// What `instance_entity` changes compared to `prefab_entity`
+    pub fn between(prefab_entity: &LevelEntity, instance_entity: &LevelEntity) -> PrefabOverride {
+        PrefabOverride {
+            target: prefab_entity.id,
+            name: Some(&instance_entity.name)
+                .filter(|name| **name != prefab_entity.name)
+                .cloned()
+                .flatten(),
+            transform: Some(instance_entity.transform)
+                .filter(|transform| *transform != prefab_entity.transform),
+            components: Some(&instance_entity.components)
+                .filter(|components| **components != prefab_entity.components)
+                .cloned(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.transform.is_none() && self.components.is_none()
+    }
+}
+impl PrefabInstance {
+    /// The prefab's entities as they look in this instance, keeping the prefab's ids
+    pub fn apply(&self, prefab: &LevelFile) -> Vec<LevelEntity> {
+        prefab
+            .entities
+            .iter()
+            .map(|prefab_entity| {
+                let mut entity = prefab_entity.clone();
+                let prefab_override = self
+                    .overrides
+                    .iter()
+                    .find(|prefab_override| prefab_override.target == entity.id);
+                if let Some(prefab_override) = prefab_override {
+                    if let Some(name) = &prefab_override.name {
+                        entity.name = Some(name.clone());
+                    }
+                    if let Some(transform) = prefab_override.transform {
+                        entity.transform = transform;
+                    }
+                    if let Some(components) = &prefab_override.components {
+                        entity.components = components.clone();
+                    }
+                }
+                entity
+            })
+            .collect()
+    }
+}
+
+/// Another level file whose entities are placed below this entity, so a large map can be split
+/// into files that different people work on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubLevelRef {
+    // Asset path of the level file, like a prefab's
+    pub path: String,
+    // The game loads the sub-level once the player is this close to the entity and unloads it
+    // again when they move away, None keeps it loaded
+    #[serde(default)]
+    pub load_distance: Option<f32>,
+}
+
+/// Gameplay data attached to a level entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LevelComponent {
+    Interactable(Interactable),
+    Player(Player),
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Parse(String),
+    // Written by a newer build than this one
+    UnsupportedVersion(u32),
+    // An absolute path of an old level that isn't inside any asset root
+    AssetOutsideRoots(String),
+}
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Parse(e) => write!(f, "invalid level file: {}", e),
+            LevelError::UnsupportedVersion(version) => write!(
+                f,
+                "level version {} is newer than the supported version {}",
+                version, CURRENT_LEVEL_VERSION
+            ),
+            LevelError::AssetOutsideRoots(path) => {
+                write!(f, "asset {} isn't inside any asset root", path)
+            }
+        }
+    }
+}
+impl std::error::Error for LevelError {}
+
+impl LevelFile {
+    /// Parse a level of any known version, upgrading it to the current one. `asset_roots` are
+    /// only used for the absolute asset paths of the oldest files
+    pub fn from_ron(level_ron: &str, asset_roots: &AssetRoots) -> Result<LevelFile, LevelError> {
+        if let Ok(header) = ron::from_str::<VersionHeader>(level_ron) {
+            return match header.version {
+                // Versions 2 and 3 only lack `prefab` and `sub_level`, which default to None
+                2 | 3 | CURRENT_LEVEL_VERSION => ron::from_str(level_ron)
+                    .map(|level: LevelFile| LevelFile {
+                        version: CURRENT_LEVEL_VERSION,
+                        ..level
+                    })
+                    .map_err(|e| LevelError::Parse(e.to_string())),
+                // Migrations from older versioned files go here
+                version => Err(LevelError::UnsupportedVersion(version)),
+            };
+        }
+        if let Ok(instances) = ron::from_str::<Vec<LegacyGltfInstance>>(level_ron) {
+            return migrate_gltf_instances(instances, asset_roots);
+        }
+        let value =
+            ron::from_str::<Value>(level_ron).map_err(|e| LevelError::Parse(e.to_string()))?;
+        migrate_dynamic_scene(&value)
+    }
+    pub fn to_ron(&self) -> Result<String, LevelError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| LevelError::Parse(e.to_string()))
+    }
+    /// The entity without a parent, which prefab instances take the place of
+    pub fn prefab_root(&self) -> Option<&LevelEntity> {
+        self.entities.iter().find(|entity| entity.parent.is_none())
+    }
+    /// The level with every prefab instance replaced by the prefab's entities, for when the
+    /// link to the prefab isn't needed. Instances whose prefab `load_prefab` can't find are
+    /// kept as empty entities
+    pub fn instantiate_prefabs(
+        &self,
+        load_prefab: &mut impl FnMut(&str) -> Option<LevelFile>,
+    ) -> LevelFile {
+        self.instantiate_prefabs_nested(load_prefab, 0)
+    }
+    fn instantiate_prefabs_nested(
+        &self,
+        load_prefab: &mut impl FnMut(&str) -> Option<LevelFile>,
+        depth: usize,
+    ) -> LevelFile {
+        // Prefab entities other than the root get ids after the level's own
+        let mut next_id = self.entities.iter().map(|entity| entity.id + 1).max();
+        let mut entities = Vec::new();
+        for entity in &self.entities {
+            let Some(instance) = &entity.prefab else {
+                entities.push(entity.clone());
+                continue;
+            };
+            let instance_root = LevelEntity {
+                prefab: None,
+                ..entity.clone()
+            };
+            let prefab = (depth < MAX_PREFAB_NESTING)
+                .then(|| load_prefab(&instance.path))
+                .flatten()
+                .map(|prefab| prefab.instantiate_prefabs_nested(load_prefab, depth + 1));
+            let Some(prefab_root_id) = prefab
+                .as_ref()
+                .and_then(|prefab| prefab.prefab_root())
+                .map(|root| root.id)
+            else {
+                entities.push(instance_root);
+                continue;
+            };
+            let prefab_entities = instance.apply(prefab.as_ref().unwrap());
+            let mut ids = HashMap::new();
+            for prefab_entity in &prefab_entities {
+                let id = if prefab_entity.id == prefab_root_id {
+                    entity.id
+                } else {
+                    let id = next_id.unwrap_or(0);
+                    next_id = Some(id + 1);
+                    id
+                };
+                ids.insert(prefab_entity.id, id);
+            }
+            for prefab_entity in prefab_entities {
+                if prefab_entity.id == prefab_root_id {
+                    entities.push(LevelEntity {
+                        name: prefab_entity.name,
+                        asset: prefab_entity.asset,
+                        components: prefab_entity.components,
+                        sub_level: prefab_entity.sub_level,
+                        ..instance_root.clone()
+                    });
+                } else {
+                    entities.push(LevelEntity {
+                        id: ids[&prefab_entity.id],
+                        parent: prefab_entity
+                            .parent
+                            .and_then(|parent| ids.get(&parent).copied()),
+                        ..prefab_entity
+                    });
+                }
+            }
+        }
+        LevelFile {
+            version: self.version,
+            entities,
+        }
+    }
+    /// Entities in an order where every parent comes before its children
+    pub fn sorted_by_hierarchy(&self) -> Vec<&LevelEntity> {
+        let mut sorted: Vec<&LevelEntity> = Vec::with_capacity(self.entities.len());
+        let mut remaining: Vec<&LevelEntity> = self.entities.iter().collect();
+        while !remaining.is_empty() {
+            let before = remaining.len();
+            remaining.retain(|entity| {
+                let parent_placed = entity.parent.is_none_or(|parent| {
+                    sorted.iter().any(|placed| placed.id == parent)
+                        || !self.entities.iter().any(|other| other.id == parent)
+                });
+                if parent_placed {
+                    sorted.push(entity);
+                }
+                !parent_placed
+            });
+            // A parent cycle, nothing more can be placed so keep the rest as they are
+            if remaining.len() == before {
+                sorted.append(&mut remaining);
+            }
+        }
+        sorted
+    }
+}
+
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u32,
+}
+
+// Version 0: what `load_gltf_instances` in the editor used to read
+#[derive(Deserialize)]
+struct LegacyGltfInstance {
+    path: String,
+    transform: LegacyTransform,
+    parent: Option<usize>,
+}
+#[derive(Deserialize)]
+struct LegacyTransform {
+    translation: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3],
+}
+fn migrate_gltf_instances(
+    instances: Vec<LegacyGltfInstance>,
+    asset_roots: &AssetRoots,
+) -> Result<LevelFile, LevelError> {
+    let entities = instances
+        .into_iter()
+        .enumerate()
+        .map(|(index, instance)| {
+            Ok(LevelEntity {
+                id: index as u64,
+                name: Some(format!("test glb {}", index)),
+                parent: instance.parent.map(|parent| parent as u64),
+                transform: LevelTransform {
+                    translation: instance.transform.translation,
+                    rotation: instance.transform.rotation,
+                    scale: instance.transform.scale,
+                },
+                asset: Some(AssetRef {
+                    path: legacy_asset_path(&instance.path, asset_roots)?,
+                    label: None,
+                }),
+                components: Vec::new(),
+                prefab: None,
+                sub_level: None,
+            })
+        })
+        .collect::<Result<_, LevelError>>()?;
+    Ok(LevelFile {
+        version: CURRENT_LEVEL_VERSION,
+        entities,
+    })
+}
+// The reference to an asset the oldest files stored the absolute path of. Looking the path up
+// in the roots rather than cutting it at an `assets` folder keeps folders named `assets`
+// above the root out of the reference
+fn legacy_asset_path(path: &str, asset_roots: &AssetRoots) -> Result<String, LevelError> {
+    asset_roots
+        .asset_path(&asset_roots.base_path.join(path))
+        .ok_or_else(|| LevelError::AssetOutsideRoots(path.to_owned()))
+}
+
+// Version 1: a Bevy DynamicScene, read component by component from its type paths
+const TRANSFORM_TYPE_PATH: &str = "bevy_transform::components::transform::Transform";
+const NAME_TYPE_PATH: &str = "bevy_ecs::name::Name";
+const CHILD_OF_TYPE_PATH: &str = "bevy_ecs::hierarchy::ChildOf";
+const EDITOR_CHILD_OF_TYPE_PATH: &str = "editor::defs::EditorChildOf";
+const GLTF_REF_TYPE_PATH: &str = "editor::defs::GltfRef";
+const INCLUDE_IN_SAVE_TYPE_PATH: &str = "editor::defs::IncludeInSave";
+
+fn migrate_dynamic_scene(scene: &Value) -> Result<LevelFile, LevelError> {
+    let scene_entities = field(scene, "entities")
+        .and_then(as_map)
+        .ok_or_else(|| LevelError::Parse("not a level or scene file".to_owned()))?;
+    let mut entities = Vec::new();
+    for (id, scene_entity) in scene_entities.iter() {
+        let (Some(id), Some(components)) = (
+            as_u64(id),
+            field(scene_entity, "components").and_then(as_map),
+        ) else {
+            continue;
+        };
+        let component = |type_path: &str| components.get(&Value::String(type_path.to_owned()));
+        // The glTF scene instances were saved too, only keep editor objects
+        if component(INCLUDE_IN_SAVE_TYPE_PATH).is_none() {
+            continue;
+        }
+        let transform = component(TRANSFORM_TYPE_PATH)
+            .map(|transform| LevelTransform {
+                translation: field(transform, "translation")
+                    .and_then(as_floats)
+                    .unwrap_or([0.0; 3]),
+                rotation: field(transform, "rotation")
+                    .and_then(as_floats)
+                    .unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                scale: field(transform, "scale")
+                    .and_then(as_floats)
+                    .unwrap_or([1.0; 3]),
+            })
+            .unwrap_or_default();
+        let parent = component(EDITOR_CHILD_OF_TYPE_PATH)
+            .or_else(|| component(CHILD_OF_TYPE_PATH))
+            .and_then(newtype_u64);
+        let asset = component(GLTF_REF_TYPE_PATH).and_then(|gltf_ref| {
+            Some(AssetRef {
+                path: field(gltf_ref, "asset_path").and_then(as_string)?,
+                label: field(gltf_ref, "label")
+                    .and_then(as_option)
+                    .and_then(as_string)
+                    .map(|label| label.trim_start_matches('#').to_owned()),
+            })
+        });
+        entities.push(LevelEntity {
+            id,
+            name: component(NAME_TYPE_PATH).and_then(as_string),
+            parent,
+            transform,
+            asset,
+            components: Vec::new(),
+            prefab: None,
+            sub_level: None,
+        });
+    }
+    // Parents that weren't editor objects (e.g. the scene root) don't exist in the level
+    let ids: Vec<u64> = entities.iter().map(|entity| entity.id).collect();
+    for entity in &mut entities {
+        entity.parent = entity.parent.filter(|parent| ids.contains(parent));
+    }
+    Ok(LevelFile {
+        version: CURRENT_LEVEL_VERSION,
+        entities,
+    })
+}
+fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    as_map(value)?.get(&Value::String(name.to_owned()))
+}
+fn as_map(value: &Value) -> Option<&Map> {
+    match value {
+        Value::Map(map) => Some(map),
+        _ => None,
+    }
+}
+fn as_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(number) => Some(number.into_f64() as u64),
+        _ => None,
+    }
+}
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(string) => Some(string.clone()),
+        _ => None,
+    }
+}
+fn as_option(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Option(Some(inner)) => Some(inner),
+        _ => None,
+    }
+}
+// Entity ids of relationship components are written as a one element tuple
+fn newtype_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Seq(items) => as_u64(items.first()?),
+        other => as_u64(other),
+    }
+}
+fn as_floats<const N: usize>(value: &Value) -> Option<[f32; N]> {
+    let Value::Seq(items) = value else {
+        return None;
+    };
+    let floats: Vec<f32> = items
+        .iter()
+        .map(|item| match item {
+            Value::Number(number) => Some(number.into_f64() as f32),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    floats.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::asset_roots::AssetRoot;
+
+    // Roots that don't exist on disk, so paths are compared as written
+    fn test_roots() -> AssetRoots {
+        AssetRoots {
+            base_path: PathBuf::from("/home/cv/assets/game/editor"),
+            extra_roots: vec![AssetRoot {
+                name: "shared".to_owned(),
+                path: PathBuf::from("../shared_assets"),
+            }],
+        }
+    }
+
+    #[test]
+    fn old_absolute_paths_become_relative_to_their_root() {
+        let level = LevelFile::from_ron(
+            r#"[
+                (path: "/home/cv/assets/game/editor/assets/test.glb", transform: (translation: (0.0, 0.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)), parent: None),
+                (path: "/home/cv/assets/game/shared_assets/props/crate.glb", transform: (translation: (0.0, 0.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)), parent: None),
+            ]"#,
+            &test_roots(),
+        )
+        .unwrap();
+        let paths: Vec<&str> = level
+            .entities
+            .iter()
+            .map(|entity| entity.asset.as_ref().unwrap().path.as_str())
+            .collect();
+        assert_eq!(paths, ["test.glb", "shared://props/crate.glb"]);
+    }
+
+    #[test]
+    fn migrates_the_gltf_instance_list() {
+        let level = LevelFile::from_ron(
+            r#"[
+                (path: "/home/cv/assets/game/editor/assets/table.glb", transform: (translation: (1.0, 0.0, -2.0), rotation: (0.0, 0.6, 0.0, 0.8), scale: (1.0, 1.0, 1.0)), parent: None),
+                (path: "/home/cv/assets/game/editor/assets/props/cup.glb", transform: (translation: (0.0, 1.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (0.5, 0.5, 0.5)), parent: Some(0)),
+            ]"#,
+            &test_roots(),
+        )
+        .unwrap();
+        assert_eq!(level.version, CURRENT_LEVEL_VERSION);
+        assert_eq!(
+            level.entities,
+            [
+                LevelEntity {
+                    id: 0,
+                    name: Some("test glb 0".to_owned()),
+                    parent: None,
+                    transform: LevelTransform {
+                        translation: [1.0, 0.0, -2.0],
+                        rotation: [0.0, 0.6, 0.0, 0.8],
+                        scale: [1.0; 3],
+                    },
+                    asset: Some(AssetRef {
+                        path: "table.glb".to_owned(),
+                        label: None,
+                    }),
+                    components: Vec::new(),
+                    prefab: None,
+                    sub_level: None,
+                },
+                LevelEntity {
+                    id: 1,
+                    name: Some("test glb 1".to_owned()),
+                    parent: Some(0),
+                    transform: LevelTransform {
+                        translation: [0.0, 1.0, 0.0],
+                        rotation: [0.0, 0.0, 0.0, 1.0],
+                        scale: [0.5; 3],
+                    },
+                    asset: Some(AssetRef {
+                        path: "props/cup.glb".to_owned(),
+                        label: None,
+                    }),
+                    components: Vec::new(),
+                    prefab: None,
+                    sub_level: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn migrates_the_dynamic_scene() {
+        // An editor object with a child object, and a glTF scene instance below each that
+        // isn't an editor object
+        let level = LevelFile::from_ron(
+            r##"(
+                resources: {},
+                entities: {
+                    4294967296: (
+                        components: {
+                            "bevy_ecs::name::Name": "Table",
+                            "bevy_transform::components::transform::Transform": (
+                                translation: (1.0, 0.0, -2.0),
+                                rotation: (0.0, 0.0, 0.0, 1.0),
+                                scale: (2.0, 2.0, 2.0),
+                            ),
+                            "editor::defs::GltfRef": (asset_path: "models/table.glb", label: Some("#Scene0")),
+                            "editor::defs::IncludeInSave": (),
+                        },
+                    ),
+                    4294967297: (
+                        components: {
+                            "bevy_ecs::hierarchy::ChildOf": (4294967296),
+                            "bevy_transform::components::transform::Transform": (
+                                translation: (0.0, 0.0, 0.0),
+                                rotation: (0.0, 0.0, 0.0, 1.0),
+                                scale: (1.0, 1.0, 1.0),
+                            ),
+                        },
+                    ),
+                    4294967298: (
+                        components: {
+                            "bevy_ecs::name::Name": "Cup",
+                            "bevy_ecs::hierarchy::ChildOf": (4294967296),
+                            "bevy_transform::components::transform::Transform": (
+                                translation: (0.0, 1.0, 0.0),
+                                rotation: (0.0, 0.0, 0.0, 1.0),
+                                scale: (1.0, 1.0, 1.0),
+                            ),
+                            "editor::defs::EditorChildOf": (4294967296),
+                            "editor::defs::GltfRef": (asset_path: "models/cup.glb", label: None),
+                            "editor::defs::IncludeInSave": (),
+                        },
+                    ),
+                    4294967299: (
+                        components: {
+                            "bevy_ecs::hierarchy::ChildOf": (4294967297),
+                        },
+                    ),
+                },
+            )"##,
+            &test_roots(),
+        )
+        .unwrap();
+        assert_eq!(level.version, CURRENT_LEVEL_VERSION);
+        let mut entities = level.entities;
+        entities.sort_by_key(|entity| entity.id);
+        assert_eq!(entities.len(), 2);
+        let (table, cup) = (&entities[0], &entities[1]);
+        assert_eq!(table.id, 4294967296);
+        assert_eq!(table.name.as_deref(), Some("Table"));
+        assert_eq!(table.parent, None);
+        assert_eq!(
+            table.transform,
+            LevelTransform {
+                translation: [1.0, 0.0, -2.0],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                scale: [2.0; 3],
+            }
+        );
+        assert_eq!(
+            table.asset,
+            Some(AssetRef {
+                path: "models/table.glb".to_owned(),
+                label: Some("Scene0".to_owned()),
+            })
+        );
+        assert_eq!(cup.id, 4294967298);
+        assert_eq!(cup.name.as_deref(), Some("Cup"));
+        assert_eq!(cup.parent, Some(4294967296));
+        assert_eq!(cup.transform.translation, [0.0, 1.0, 0.0]);
+        assert_eq!(
+            cup.asset,
+            Some(AssetRef {
+                path: "models/cup.glb".to_owned(),
+                label: None,
+            })
+        );
+    }
+
+    #[test]
+    fn newer_versions_are_an_error() {
+        let level = LevelFile::from_ron(
+            &format!("(version: {}, entities: [])", CURRENT_LEVEL_VERSION + 1),
+            &test_roots(),
+        );
+        assert!(matches!(
+            level,
+            Err(LevelError::UnsupportedVersion(version)) if version == CURRENT_LEVEL_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn parses_asset_labels() {
+        assert_eq!(AssetLabel::parse("Scene0"), Some(AssetLabel::Scene(0)));
+        assert_eq!(AssetLabel::parse("Node12"), Some(AssetLabel::Node(12)));
+        assert_eq!(AssetLabel::parse("Mesh3"), Some(AssetLabel::Mesh(3)));
+        // Other glTF labels, or a part without its index
+        assert_eq!(AssetLabel::parse("Mesh3/Primitive0"), None);
+        assert_eq!(AssetLabel::parse("Animation1"), None);
+        assert_eq!(AssetLabel::parse("Node"), None);
+    }
+
+    #[test]
+    fn old_paths_outside_every_root_are_an_error() {
+        let level = LevelFile::from_ron(
+            r#"[
+                (path: "/home/someone/elsewhere/assets/test.glb", transform: (translation: (0.0, 0.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)), parent: None),
+            ]"#,
+            &test_roots(),
+        );
+        assert!(matches!(
+            level,
+            Err(LevelError::AssetOutsideRoots(path)) if path == "/home/someone/elsewhere/assets/test.glb"
+        ));
+    }
+}
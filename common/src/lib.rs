@@ -1,15 +1,29 @@
+#[cfg(feature = "bevy")]
+use bevy::prelude::{Component, Reflect, ReflectComponent, ReflectDefault};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+pub mod asset_roots;
+pub mod level;
+
+// Gameplay components placed in the editor and saved with the level. With the `bevy` feature
+// they're reflected components the editor inspector can attach, the game wraps them in its own
+// components since it's on a different bevy version
+
+// Marks where the player starts
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component, Default))]
 pub struct Player;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "bevy", derive(Component, Reflect), reflect(Component, Default))]
 pub struct Interactable {
     pub kind: InteractableType,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "bevy", derive(Reflect), reflect(Default))]
 pub enum InteractableType {
+    #[default]
     Door,
     Lever,
     Terminal,
@@ -0,0 +1,142 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use bevy::{
+    asset::{
+        io::file::FileAssetReader,
+        ron::{self, ser::PrettyConfig},
+    },
+    prelude::*,
+};
+use common::level::LevelFile;
+use rfd::{MessageButtons, MessageDialog, MessageDialogResult};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    defs::{AutosaveSettings, CurrentLevel, EditorStatus},
+    editor_history::EditorHistory,
+    saving_loading::{clear_level, editor_level, spawn_level, write_atomically},
+};
+// Next to the editor's assets folder, there is only one per editor install
+const RECOVERY_FILE: &str = "autosave_recovery.ron";
+
+pub struct AutosavePlugin;
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        let recovery_path = FileAssetReader::get_base_path().join(RECOVERY_FILE);
+        app.insert_resource(AutosaveSettings::new(recovery_path))
+            .add_systems(Update, autosave);
+    }
+}
+// The unsaved level and which file it belongs to, None for a level that was never saved
+#[derive(Serialize, Deserialize)]
+struct Recovery {
+    level_path: Option<PathBuf>,
+    level: LevelFile,
+}
+
+fn autosave(
+    time: Res<Time>,
+    settings: Res<AutosaveSettings>,
+    history: Res<EditorHistory>,
+    mut dirty_seconds: Local<f32>,
+    mut commands: Commands,
+) {
+    // Count from the first unsaved edit, not from the last save
+    if !settings.enabled || !history.is_dirty() {
+        *dirty_seconds = 0.0;
+        return;
+    }
+    *dirty_seconds += time.delta_secs();
+    if *dirty_seconds >= settings.interval_seconds {
+        *dirty_seconds = 0.0;
+        commands.run_system_cached(write_recovery_file);
+    }
+}
+fn write_recovery_file(world: &mut World) {
+    let recovery_path = world.resource::<AutosaveSettings>().recovery_path.clone();
+    let recovery = Recovery {
+        level_path: world.resource::<CurrentLevel>().path.clone(),
+        level: editor_level(world),
+    };
+    let written = ron::ser::to_string_pretty(&recovery, PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|ron| write_atomically(&recovery_path, &ron).map_err(|e| e.to_string()));
+    let mut status = world.resource_mut::<EditorStatus>();
+    match written {
+        Ok(()) => status.info("Autosaved unsaved changes"),
+        Err(e) => status.error(format!(
+            "Error autosaving to {}: {}",
+            recovery_path.display(),
+            e
+        )),
+    }
+}
+/// Forget the autosaved changes once the level they belong to is saved or closed
+pub fn remove_recovery_file(world: &World) {
+    if let Some(settings) = world.get_resource::<AutosaveSettings>() {
+        fs::remove_file(&settings.recovery_path).ok();
+    }
+}
+/// Ask whether to restore changes autosaved by a session that ended without saving them.
+/// Returns true if they were restored
+pub fn restore_recovery(world: &mut World) -> bool {
+    let Some(recovery_path) = world
+        .get_resource::<AutosaveSettings>()
+        .map(|settings| settings.recovery_path.clone())
+    else {
+        return false;
+    };
+    let Ok(recovery_ron) = fs::read_to_string(&recovery_path) else {
+        return false;
+    };
+    let recovery: Recovery = match ron::from_str(&recovery_ron) {
+        Ok(recovery) => recovery,
+        Err(e) => {
+            world.resource_mut::<EditorStatus>().error(format!(
+                "Error reading autosaved changes {}: {}",
+                recovery_path.display(),
+                e
+            ));
+            return false;
+        }
+    };
+    // Saving the level normally removes the recovery file, but it may have been saved
+    // by another editor since
+    let modified =
+        |path: &PathBuf| -> Option<SystemTime> { fs::metadata(path).ok()?.modified().ok() };
+    let newer_than_save = match (
+        modified(&recovery_path),
+        recovery.level_path.as_ref().and_then(modified),
+    ) {
+        (Some(recovered_at), Some(saved_at)) => recovered_at > saved_at,
+        _ => true,
+    };
+    let level_name = recovery
+        .level_path
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "an untitled level".to_owned());
+    let restore = newer_than_save
+        && MessageDialog::new()
+            .set_title("Recover unsaved changes")
+            .set_description(format!(
+                "The editor closed with unsaved changes to {}. Restore them?",
+                level_name
+            ))
+            .set_buttons(MessageButtons::YesNo)
+            .show()
+            == MessageDialogResult::Yes;
+    if !restore {
+        fs::remove_file(&recovery_path).ok();
+        return false;
+    }
+    // The recovery file stays until the restored level is saved
+    clear_level(world);
+    spawn_level(world, &recovery.level);
+    world.resource_mut::<CurrentLevel>().path = recovery.level_path;
+    world.resource_mut::<EditorHistory>().mark_unsaved();
+    world
+        .resource_mut::<EditorStatus>()
+        .info(format!("Restored unsaved changes to {}", level_name));
+    true
+}
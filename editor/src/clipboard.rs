@@ -0,0 +1,109 @@
+use bevy::{
+    picking::mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_egui::EguiClipboard;
+use common::level::LevelFile;
+
+use crate::{
+    camera::EditorCamera,
+    defs::{EditorAssetRoots, EditorChildOf, EditorSelected},
+    editor_history::{EditorEdit, EditorHistory},
+    input::HighlightedMesh,
+    saving_loading::{editor_subtrees, level_from_world, spawn_level},
+};
+// How far in front of the camera objects are pasted when the cursor isn't over anything
+const PASTE_FALLBACK_DISTANCE: f32 = 10.0;
+
+/// Put the selected objects and their editor children on the clipboard, in the same
+/// level format that saving writes
+pub fn copy_selection_to_clipboard(world: &mut World) {
+    let selected = world.resource::<EditorSelected>().0.clone();
+    let entities = editor_subtrees(world, selected);
+    if entities.is_empty() {
+        return;
+    }
+    match level_from_world(world, &entities).to_ron() {
+        Ok(ron) => {
+            world.resource_mut::<EguiClipboard>().set_text(&ron);
+        }
+        Err(e) => {
+            eprintln!("Error serializing the selection: {}", e);
+        }
+    }
+}
+
+/// Spawn the objects on the clipboard with new ids, centered on the point under the cursor
+pub fn paste_from_clipboard(world: &mut World) {
+    let Some(text) = world.resource_mut::<EguiClipboard>().get_text() else {
+        return;
+    };
+    let level = match LevelFile::from_ron(&text, world.resource::<EditorAssetRoots>()) {
+        Ok(level) => level,
+        Err(e) => {
+            eprintln!("Clipboard doesn't contain editor objects: {}", e);
+            return;
+        }
+    };
+    // Top level objects were stored where they were, even if they had a parent when copied
+    let roots: Vec<Entity> = spawn_level(world, &level)
+        .into_iter()
+        .filter(|entity| world.get::<EditorChildOf>(*entity).is_none())
+        .collect();
+    if roots.is_empty() {
+        return;
+    }
+
+    // Keep the layout of the copied objects, moving their center to the cursor
+    let center = roots
+        .iter()
+        .filter_map(|root| world.get::<Transform>(*root))
+        .map(|transform| transform.translation)
+        .sum::<Vec3>()
+        / roots.len() as f32;
+    if let Some(target) = world.run_system_cached(cursor_hit_point).ok().flatten() {
+        for root in &roots {
+            if let Some(mut transform) = world.get_mut::<Transform>(*root) {
+                transform.translation += target - center;
+            }
+        }
+    }
+
+    let spawns = roots
+        .iter()
+        .map(|root| EditorEdit::Spawn {
+            root: *root,
+            snapshot: Vec::new(),
+        })
+        .collect();
+    world
+        .resource_mut::<EditorHistory>()
+        .push(EditorEdit::Group(spawns));
+    world.resource_mut::<EditorSelected>().0 = roots;
+}
+// Point on the mesh under the cursor, or on the ground plane / in front of the camera
+pub fn cursor_hit_point(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    cam_q: Query<(&GlobalTransform, &Camera), With<EditorCamera>>,
+    mut mesh_ray_cast: MeshRayCast,
+    highlights_q: Query<(), With<HighlightedMesh>>,
+) -> Option<Vec3> {
+    let window = window_q.single().ok()?;
+    let (cam_transform, camera) = cam_q.single().ok()?;
+    let cursor_position = window.cursor_position()?;
+    let ray = camera
+        .viewport_to_world(cam_transform, cursor_position)
+        .ok()?;
+    let filter = |entity: Entity| !highlights_q.contains(entity);
+    let settings = MeshRayCastSettings::default()
+        .with_filter(&filter)
+        .always_early_exit();
+    if let Some((_, hit)) = mesh_ray_cast.cast_ray(ray, &settings).first() {
+        return Some(hit.point);
+    }
+    let ground_distance = ray
+        .intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y))
+        .unwrap_or(PASTE_FALLBACK_DISTANCE);
+    Some(ray.get_point(ground_distance))
+}
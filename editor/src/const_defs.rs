@@ -3,3 +3,7 @@ pub const PLAYER_COLLIDER_HEIGHT: f32 = 1.0;
 pub const GROUND_TIMER: f32 = 0.1;
 pub const JUMP_SPEED: f32 = 0.04;
 pub const GRAVITY: f32 = -0.01;
+// Folder inside the default asset root that files from outside the asset roots are copied to
+pub const IMPORTED_ASSETS_FOLDER: &str = "imported";
+// Folder inside the default asset root the prefab dialogs start in
+pub const PREFABS_FOLDER: &str = "prefabs";
@@ -1,5 +1,10 @@
 use bevy::prelude::*;
+use common::{
+    asset_roots::AssetRoots,
+    level::{LevelFile, PrefabOverride},
+};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 use crate::outline_material::OutlineMaterial;
 
@@ -24,84 +29,255 @@ pub enum EditorAxis {
     Y,
     Z,
 }
+// Point that a multi-object rotation/scale happens around
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PivotPoint {
+    #[default]
+    MedianPoint,
+    ActiveObject,
+}
 #[derive(Resource, Default)]
 pub struct CurrentObjectManipulationMode {
     pub mode: ManipulationMode,
     pub axis_restriction: Option<EditorAxis>,
+    // Move/scale along the two axes other than this one
+    pub plane_restriction: Option<EditorAxis>,
     pub local: bool,
+    pub pivot: PivotPoint,
+}
+impl CurrentObjectManipulationMode {
+    // A typed distance needs a single direction, a move in a plane has two
+    pub fn takes_typed_value(&self) -> bool {
+        !(self.mode == ManipulationMode::Move && self.plane_restriction.is_some())
+    }
+}
+// Which handles the transform gizmo shows when nothing is being manipulated
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoTool {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoHandle {
+    // Arrow moving along an axis
+    Axis(EditorAxis),
+    // Square moving in the plane perpendicular to the axis
+    Plane(EditorAxis),
+    // Ring rotating around an axis
+    Ring(EditorAxis),
+    // Cube tipped handle scaling along an axis
+    ScaleAxis(EditorAxis),
+    // Center handle scaling on all axes
+    ScaleUniform,
+}
+#[derive(Resource, Default)]
+pub struct TransformGizmoState {
+    pub tool: GizmoTool,
+    pub hovered: Option<GizmoHandle>,
+    // The handle being dragged, the manipulation is confirmed when the mouse is released
+    pub dragging: Option<GizmoHandle>,
+}
+// What a moved object snaps to, rotation and scale always snap to increments
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapTarget {
+    #[default]
+    Increment,
+    // The surface under the cursor
+    Surface,
+    // The closest vertex of the mesh under the cursor
+    Vertex,
+}
+// Increments that move/rotate/scale snap to, holding Ctrl inverts `enabled` during a manipulation
+#[derive(Resource)]
+pub struct SnapSettings {
+    pub enabled: bool,
+    pub target: SnapTarget,
+    pub translation_step: f32,
+    pub rotation_step_degrees: f32,
+    pub scale_step: f32,
+    // Snap the bottom of the bounding box to the surface instead of the origin
+    pub snap_bounds_bottom: bool,
+    // Rotate the up axis of the active object to the surface normal
+    pub align_to_normal: bool,
+}
+impl Default for SnapSettings {
+    fn default() -> Self {
+        SnapSettings {
+            enabled: false,
+            target: SnapTarget::Increment,
+            translation_step: 1.0,
+            rotation_step_degrees: 15.0,
+            scale_step: 0.1,
+            snap_bounds_bottom: true,
+            align_to_normal: false,
+        }
+    }
 }
 #[derive(Component, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Component, Default)]
 pub struct IncludeInSave;
-#[derive(Component, Reflect, Serialize, Deserialize)]
+// Folders asset references are relative to, see common::asset_roots
+#[derive(Resource, Deref)]
+pub struct EditorAssetRoots(pub AssetRoots);
+// The level file being edited, None until a new level is saved for the first time
+#[derive(Resource, Default)]
+pub struct CurrentLevel {
+    pub path: Option<PathBuf>,
+}
+#[derive(Component, Reflect, Serialize, Deserialize, Clone)]
 #[reflect(Component)]
 pub struct GltfRef {
     // The path to the original glTF file
     pub asset_path: String,
-    // The ID of the scene/node within the glTF file, e.g., "#Scene0"
+    // Asset label of the scene, node or mesh within the glTF file, e.g. "Scene0", "Node3" or
+    // "Mesh1". None is the first scene
     pub label: Option<String>,
 }
+// Root of a prefab instance, the prefab's other objects are below it with a PrefabLink
+#[derive(Component, Clone)]
+pub struct PrefabRoot {
+    // Asset path of the prefab file, like a GltfRef's
+    pub path: String,
+    // The prefab as it was when the instance was spawned, with the prefabs inside it
+    // instantiated. Overrides are saved against it, so changes made to the file since don't
+    // turn into overrides. None when the prefab couldn't be loaded
+    pub prefab: Option<LevelFile>,
+    // The overrides from the level file when the prefab couldn't be loaded, saved unchanged
+    pub unresolved_overrides: Option<Vec<PrefabOverride>>,
+}
+// Object spawned from a prefab, whatever differs from entity `source_id` of the prefab file
+// is saved as an override
+#[derive(Component, Clone, Copy)]
+pub struct PrefabLink {
+    pub source_id: u64,
+}
+// Object the contents of another level file are loaded below, they're saved back to that file
+#[derive(Component, Clone)]
+pub struct SubLevelRoot {
+    // Asset path of the level file
+    pub path: String,
+    pub load_distance: Option<f32>,
+    // The file as it was on disk when it was loaded or last saved. If it's different when
+    // saving, someone else changed it and it isn't overwritten
+    pub saved_file: String,
+    // The contents as they'd be saved at that point, the file is only written if they changed
+    pub saved_level: String,
+}
 
 #[derive(Component)]
 pub struct FinishedGltfRefLoading;
 #[derive(Debug, Resource, Default)]
 pub struct MoveState {
     pub start_cursor: Vec2,
+    // The pivot of the manipulation, rotated like the active object so local axes follow it
     pub start_transform: GlobalTransform,
-    pub start_local_transform: Transform,
     pub start_depth: f32,
+    // Every entity being manipulated, used to apply the change to each and to revert it
+    pub selection_start: Vec<SelectionStartTransform>,
+    // Exact offset/angle/factor typed during the manipulation, e.g. "-1.5"
+    pub typed_input: String,
+}
+impl MoveState {
+    pub fn typed_value(&self) -> Option<f32> {
+        if self.typed_input.is_empty() {
+            return None;
+        }
+        // Treat partial input like "-" or "." as zero until more is typed
+        Some(self.typed_input.parse().unwrap_or(0.0))
+    }
+}
+#[derive(Debug, Clone, Copy)]
+pub struct SelectionStartTransform {
+    pub entity: Entity,
+    pub global: GlobalTransform,
+    pub local: Transform,
 }
 #[derive(Component)]
 pub struct EditorEntityLink(pub Entity);
 #[derive(Component)]
 pub struct GltfEntityRoot(pub Entity);
-// New type so we can serialize the transform
-#[derive(Serialize, Deserialize, Debug)]
-pub struct EditorGltfInstance {
-    pub path: String, // Path to the GLTF or scene asset
-    pub transform: TransformData,
-    pub parent: Option<usize>, // Index in the Vec during save/load
-}
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TransformData {
-    pub translation: Vec3,
-    pub rotation: Quat,
-    pub scale: Vec3,
-}
-impl From<Transform> for TransformData {
-    fn from(t: Transform) -> Self {
-        Self {
-            translation: t.translation,
-            rotation: t.rotation,
-            scale: t.scale,
+#[derive(Component)]
+pub struct EditorChildOf(pub Entity);
+// Selected editor objects in selection order, the last one is the active object
+#[derive(Resource, Default)]
+pub struct EditorSelected(pub Vec<Entity>);
+impl EditorSelected {
+    pub fn active(&self) -> Option<Entity> {
+        self.0.last().copied()
+    }
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+    pub fn select_only(&mut self, entity: Entity) {
+        self.0.clear();
+        self.0.push(entity);
+    }
+    /// Add the entity as the active object, or remove it if it is already selected
+    pub fn toggle(&mut self, entity: Entity) {
+        if let Some(index) = self.0.iter().position(|selected| *selected == entity) {
+            self.0.remove(index);
+        } else {
+            self.0.push(entity);
         }
     }
 }
-impl From<TransformData> for Transform {
-    fn from(t: TransformData) -> Self {
-        Transform {
-            translation: t.translation,
-            rotation: t.rotation,
-            scale: t.scale,
+// Unsaved changes are written to `recovery_path` every `interval_seconds`, the next start
+// offers to restore them if the editor didn't get to save
+#[derive(Resource)]
+pub struct AutosaveSettings {
+    pub enabled: bool,
+    pub interval_seconds: f32,
+    pub recovery_path: PathBuf,
+}
+impl AutosaveSettings {
+    pub fn new(recovery_path: PathBuf) -> Self {
+        AutosaveSettings {
+            enabled: true,
+            interval_seconds: 60.0,
+            recovery_path,
         }
     }
 }
+// Last result of saving/loading, shown in the status bar until the next one
 #[derive(Resource, Default)]
-pub struct EditorGltfInstances(pub Vec<InstantiatedGltfInstance>);
-
-pub struct InstantiatedGltfInstance {
-    pub path: String,
-    pub entity: Entity,
-    pub parent: Option<usize>,
+pub struct EditorStatus {
+    pub message: Option<StatusMessage>,
+}
+pub struct StatusMessage {
+    pub text: String,
+    pub is_error: bool,
+}
+impl EditorStatus {
+    pub fn info(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        println!("{}", text);
+        self.message = Some(StatusMessage {
+            text,
+            is_error: false,
+        });
+    }
+    pub fn error(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        eprintln!("{}", text);
+        self.message = Some(StatusMessage {
+            text,
+            is_error: true,
+        });
+    }
 }
-#[derive(Component)]
-pub struct EditorChildOf(pub Entity);
-#[derive(Resource, Default)]
-pub struct EditorSelected(pub Option<Entity>);
 #[derive(Resource, Default)]
 pub struct UiBuffers {
     pub search_buf: String,
     pub rename_buf: String,
+    pub component_search_buf: String,
 }
 
 #[derive(Resource, Default)]
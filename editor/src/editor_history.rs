@@ -0,0 +1,425 @@
+use std::{any::TypeId, collections::HashMap};
+
+use bevy::prelude::*;
+
+use common::level::LevelComponent;
+
+use crate::{
+    defs::{
+        EditorChildOf, EditorSelected, GltfRef, IncludeInSave, PrefabLink, PrefabRoot,
+        RotationEditState, SubLevelRoot,
+    },
+    saving_loading::{gameplay_components, insert_gameplay_components},
+};
+
+// Oldest edits get dropped once the undo stack grows past this
+const MAX_HISTORY_LENGTH: usize = 200;
+
+/// Reversible record of every confirmed edit made in the editor.
+/// Undo pops from `undo_stack` onto `redo_stack` and vice versa.
+#[derive(Resource)]
+pub struct EditorHistory {
+    pub undo_stack: Vec<EditorEdit>,
+    pub redo_stack: Vec<EditorEdit>,
+    // Entities that were despawned and later restored get a new id, so older edits
+    // referencing the old id are redirected through this map
+    remapped_entities: HashMap<Entity, Entity>,
+    // Length of the undo stack when the level was last saved, None once that state can't
+    // be reached with undo/redo anymore
+    saved_at: Option<usize>,
+}
+impl Default for EditorHistory {
+    fn default() -> Self {
+        EditorHistory {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            remapped_entities: HashMap::new(),
+            saved_at: Some(0),
+        }
+    }
+}
+impl EditorHistory {
+    pub fn push(&mut self, edit: EditorEdit) {
+        // The saved state was in the redo stack that's about to be dropped
+        if self
+            .saved_at
+            .is_some_and(|saved_at| saved_at > self.undo_stack.len())
+        {
+            self.saved_at = None;
+        }
+        self.redo_stack.clear();
+        self.undo_stack.push(edit);
+        if self.undo_stack.len() > MAX_HISTORY_LENGTH {
+            self.undo_stack.remove(0);
+            self.saved_at = self.saved_at.and_then(|saved_at| saved_at.checked_sub(1));
+        }
+    }
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.undo_stack.len());
+    }
+    // For levels restored from somewhere other than their file, e.g. a crash recovery
+    pub fn mark_unsaved(&mut self) {
+        self.saved_at = None;
+    }
+    /// Whether there are edits that haven't been saved yet
+    pub fn is_dirty(&self) -> bool {
+        self.saved_at != Some(self.undo_stack.len())
+    }
+    /// Follow the remap chain to the entity currently representing `entity`
+    pub fn resolve(&self, mut entity: Entity) -> Entity {
+        while let Some(remapped) = self.remapped_entities.get(&entity) {
+            entity = *remapped;
+        }
+        entity
+    }
+}
+
+/// Where an editor object sits in the hierarchy, used to undo drag-drop reparenting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HierarchyState {
+    pub parent: Option<Entity>,
+    pub transform: Transform,
+}
+
+/// Everything needed to respawn an editor object after it has been despawned
+#[derive(Clone)]
+pub struct EntitySnapshot {
+    pub entity: Entity,
+    pub name: Option<Name>,
+    pub transform: Transform,
+    pub gltf_ref: Option<GltfRef>,
+    pub scene_root: Option<SceneRoot>,
+    pub gameplay: Vec<LevelComponent>,
+    pub prefab_root: Option<PrefabRoot>,
+    pub prefab_link: Option<PrefabLink>,
+    pub sub_level: Option<SubLevelRoot>,
+    pub editor_parent: Option<Entity>,
+}
+
+pub enum EditorEdit {
+    Transform {
+        entity: Entity,
+        before: Transform,
+        after: Transform,
+    },
+    Rename {
+        entity: Entity,
+        before: Option<Name>,
+        after: Option<Name>,
+    },
+    Reparent {
+        entity: Entity,
+        before: HierarchyState,
+        after: HierarchyState,
+    },
+    // How close the game streams a sub-level in from, None keeps it loaded
+    LoadDistance {
+        entity: Entity,
+        before: Option<f32>,
+        after: Option<f32>,
+    },
+    // The snapshot is (re)taken every time the subtree gets despawned, so it's empty
+    // until the spawn has been undone once
+    Spawn {
+        root: Entity,
+        snapshot: Vec<EntitySnapshot>,
+    },
+    Delete {
+        root: Entity,
+        snapshot: Vec<EntitySnapshot>,
+    },
+    // A reflected component edited, added (no before) or removed (no after) in the inspector
+    Component {
+        entity: Entity,
+        type_id: TypeId,
+        before: Option<Box<dyn PartialReflect>>,
+        after: Option<Box<dyn PartialReflect>>,
+    },
+    // Several edits made by one action, e.g. moving a multi-selection
+    Group(Vec<EditorEdit>),
+}
+
+pub fn undo_last_edit(world: &mut World) {
+    world.resource_scope(|world, mut history: Mut<EditorHistory>| {
+        let Some(mut edit) = history.undo_stack.pop() else {
+            return;
+        };
+        apply_edit(world, &mut history, &mut edit, true);
+        history.redo_stack.push(edit);
+    });
+    refresh_selection(world);
+}
+pub fn redo_last_edit(world: &mut World) {
+    world.resource_scope(|world, mut history: Mut<EditorHistory>| {
+        let Some(mut edit) = history.redo_stack.pop() else {
+            return;
+        };
+        apply_edit(world, &mut history, &mut edit, false);
+        history.undo_stack.push(edit);
+    });
+    refresh_selection(world);
+}
+fn apply_edit(world: &mut World, history: &mut EditorHistory, edit: &mut EditorEdit, undo: bool) {
+    match edit {
+        EditorEdit::Transform {
+            entity,
+            before,
+            after,
+        } => {
+            let target = if undo { *before } else { *after };
+            if let Some(mut transform) = world.get_mut::<Transform>(history.resolve(*entity)) {
+                *transform = target;
+            }
+        }
+        EditorEdit::Rename {
+            entity,
+            before,
+            after,
+        } => {
+            let target = if undo { before.clone() } else { after.clone() };
+            let Ok(mut entity_mut) = world.get_entity_mut(history.resolve(*entity)) else {
+                return;
+            };
+            match target {
+                Some(name) => {
+                    entity_mut.insert(name);
+                }
+                None => {
+                    entity_mut.remove::<Name>();
+                }
+            }
+        }
+        EditorEdit::Reparent {
+            entity,
+            before,
+            after,
+        } => {
+            let target = if undo { *before } else { *after };
+            let parent = target.parent.map(|parent| history.resolve(parent));
+            let Ok(mut entity_mut) = world.get_entity_mut(history.resolve(*entity)) else {
+                return;
+            };
+            entity_mut.insert(target.transform);
+            match parent {
+                Some(parent) => {
+                    entity_mut.insert((EditorChildOf(parent), ChildOf(parent)));
+                }
+                None => {
+                    entity_mut.remove::<(EditorChildOf, ChildOf)>();
+                }
+            }
+        }
+        EditorEdit::LoadDistance {
+            entity,
+            before,
+            after,
+        } => {
+            let target = if undo { *before } else { *after };
+            if let Some(mut sub_level) = world.get_mut::<SubLevelRoot>(history.resolve(*entity)) {
+                sub_level.load_distance = target;
+            }
+        }
+        EditorEdit::Spawn { root, snapshot } => {
+            if undo {
+                *snapshot = despawn_subtree(world, history.resolve(*root));
+            } else {
+                *root = respawn_subtree(world, history, snapshot);
+            }
+        }
+        EditorEdit::Delete { root, snapshot } => {
+            if undo {
+                *root = respawn_subtree(world, history, snapshot);
+            } else {
+                *snapshot = despawn_subtree(world, history.resolve(*root));
+            }
+        }
+        EditorEdit::Component {
+            entity,
+            type_id,
+            before,
+            after,
+        } => {
+            let target = if undo { before } else { after };
+            let type_registry = world.resource::<AppTypeRegistry>().clone();
+            let registry = type_registry.read();
+            let Some(reflect_component) = registry.get_type_data::<ReflectComponent>(*type_id)
+            else {
+                return;
+            };
+            let Ok(mut entity_mut) = world.get_entity_mut(history.resolve(*entity)) else {
+                return;
+            };
+            match target {
+                Some(value) if reflect_component.contains(&entity_mut) => {
+                    reflect_component.apply(&mut entity_mut, value.as_ref());
+                }
+                Some(value) => {
+                    reflect_component.insert(&mut entity_mut, value.as_ref(), &registry);
+                }
+                None => {
+                    reflect_component.remove(&mut entity_mut);
+                }
+            }
+        }
+        EditorEdit::Group(edits) => {
+            // Undo in the reverse order the edits were made in
+            if undo {
+                for edit in edits.iter_mut().rev() {
+                    apply_edit(world, history, edit, undo);
+                }
+            } else {
+                for edit in edits.iter_mut() {
+                    apply_edit(world, history, edit, undo);
+                }
+            }
+        }
+    }
+}
+
+/// Record the editor objects under `root` (parents first) and despawn them
+pub fn despawn_subtree(world: &mut World, root: Entity) -> Vec<EntitySnapshot> {
+    let mut snapshot = Vec::new();
+    snapshot_recursive(world, root, &mut snapshot);
+    if let Ok(entity_mut) = world.get_entity_mut(root) {
+        entity_mut.despawn();
+    }
+    snapshot
+}
+fn snapshot_recursive(world: &World, entity: Entity, snapshot: &mut Vec<EntitySnapshot>) {
+    let Ok(entity_ref) = world.get_entity(entity) else {
+        return;
+    };
+    if !entity_ref.contains::<IncludeInSave>() {
+        return;
+    }
+    snapshot.push(EntitySnapshot {
+        entity,
+        name: entity_ref.get::<Name>().cloned(),
+        transform: entity_ref.get::<Transform>().copied().unwrap_or_default(),
+        gltf_ref: entity_ref.get::<GltfRef>().cloned(),
+        scene_root: entity_ref.get::<SceneRoot>().cloned(),
+        gameplay: gameplay_components(entity_ref),
+        prefab_root: entity_ref.get::<PrefabRoot>().cloned(),
+        prefab_link: entity_ref.get::<PrefabLink>().copied(),
+        sub_level: entity_ref.get::<SubLevelRoot>().cloned(),
+        editor_parent: entity_ref.get::<EditorChildOf>().map(|parent| parent.0),
+    });
+    if let Some(children) = entity_ref.get::<Children>() {
+        for child in children.iter() {
+            snapshot_recursive(world, child, snapshot);
+        }
+    }
+}
+/// Spawn the snapshotted entities again and return the new id of the root
+fn respawn_subtree(
+    world: &mut World,
+    history: &mut EditorHistory,
+    snapshot: &[EntitySnapshot],
+) -> Entity {
+    let mut root = Entity::PLACEHOLDER;
+    for entity_snapshot in snapshot {
+        let parent = entity_snapshot
+            .editor_parent
+            .map(|parent| history.resolve(parent));
+        let new_entity = spawn_from_snapshot(world, entity_snapshot, parent);
+        history
+            .remapped_entities
+            .insert(entity_snapshot.entity, new_entity);
+        if root == Entity::PLACEHOLDER {
+            root = new_entity;
+        }
+    }
+    root
+}
+/// Spawn a copy of the editor objects under `root` next to the original, giving every copy
+/// an unused name. Returns the copy of the root
+pub fn duplicate_subtree(world: &mut World, root: Entity) -> Option<Entity> {
+    let mut snapshot = Vec::new();
+    snapshot_recursive(world, root, &mut snapshot);
+    let mut taken_names: Vec<String> = world
+        .query_filtered::<&Name, With<IncludeInSave>>()
+        .iter(world)
+        .map(|name| name.as_str().to_owned())
+        .collect();
+    let mut copies: HashMap<Entity, Entity> = HashMap::new();
+    for entity_snapshot in &mut snapshot {
+        if let Some(name) = &entity_snapshot.name {
+            let new_name = unique_name(name.as_str(), &taken_names);
+            taken_names.push(new_name.clone());
+            entity_snapshot.name = Some(Name::new(new_name));
+        }
+        // Children follow their copied parent, the root stays under the original's parent
+        let parent = entity_snapshot
+            .editor_parent
+            .map(|parent| copies.get(&parent).copied().unwrap_or(parent));
+        let copy = spawn_from_snapshot(world, entity_snapshot, parent);
+        copies.insert(entity_snapshot.entity, copy);
+    }
+    copies.get(&root).copied()
+}
+fn spawn_from_snapshot(
+    world: &mut World,
+    entity_snapshot: &EntitySnapshot,
+    parent: Option<Entity>,
+) -> Entity {
+    let mut entity_mut = world.spawn((
+        entity_snapshot.transform,
+        Visibility::Visible,
+        IncludeInSave,
+    ));
+    if let Some(name) = &entity_snapshot.name {
+        entity_mut.insert(name.clone());
+    }
+    // finish_loading_scene spawns the glTF scene again since FinishedGltfRefLoading is missing
+    if let Some(gltf_ref) = &entity_snapshot.gltf_ref {
+        entity_mut.insert(gltf_ref.clone());
+    }
+    if let Some(scene_root) = &entity_snapshot.scene_root {
+        entity_mut.insert(scene_root.clone());
+    }
+    insert_gameplay_components(&mut entity_mut, &entity_snapshot.gameplay);
+    if let Some(prefab_root) = &entity_snapshot.prefab_root {
+        entity_mut.insert(prefab_root.clone());
+    }
+    if let Some(prefab_link) = entity_snapshot.prefab_link {
+        entity_mut.insert(prefab_link);
+    }
+    if let Some(sub_level) = &entity_snapshot.sub_level {
+        entity_mut.insert(sub_level.clone());
+    }
+    if let Some(parent) = parent {
+        entity_mut.insert((EditorChildOf(parent), ChildOf(parent)));
+    }
+    entity_mut.id()
+}
+// "Chair" and "Chair.001" both become the first free "Chair.NNN"
+fn unique_name(name: &str, taken_names: &[String]) -> String {
+    let base = match name.rsplit_once('.') {
+        Some((base, suffix))
+            if !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            base
+        }
+        _ => name,
+    };
+    (1..)
+        .map(|number| format!("{base}.{number:03}"))
+        .find(|candidate| !taken_names.contains(candidate))
+        .unwrap()
+}
+// Drop selected entities that undo/redo despawned, and make the inspector re-read the transform
+fn refresh_selection(world: &mut World) {
+    let selected = world.resource::<EditorSelected>().0.clone();
+    let remaining: Vec<Entity> = selected
+        .iter()
+        .copied()
+        .filter(|entity| world.get_entity(*entity).is_ok())
+        .collect();
+    if remaining.len() != selected.len() {
+        world.resource_mut::<EditorSelected>().0 = remaining;
+    }
+    let mut rotation_edit_state = world.resource_mut::<RotationEditState>();
+    rotation_edit_state.initial_global_transform = None;
+    rotation_edit_state.initial_transform = None;
+    rotation_edit_state.rotation_edit_euler = None;
+}
@@ -1,188 +1,399 @@
 use bevy::{prelude::*, window::PrimaryWindow};
-use rfd::FileDialog;
 
 use crate::{
     camera::EditorCamera,
+    clipboard::{copy_selection_to_clipboard, paste_from_clipboard},
     defs::{
-        CurrentObjectManipulationMode, EditorAxis, EditorGltfInstances, EditorSelected,
-        IncludeInSave, InstantiatedGltfInstance, ManipulationMode, MoveState, RotationEditState,
+        CurrentObjectManipulationMode, EditorAxis, EditorChildOf, EditorSelected, IncludeInSave,
+        ManipulationMode, MoveState, PivotPoint, SelectionStartTransform, SnapSettings,
+    },
+    editor_history::{
+        EditorEdit, EditorHistory, despawn_subtree, duplicate_subtree, redo_last_edit,
+        undo_last_edit,
+    },
+    helper_funcs::top_level_selection,
+    missing_assets::{
+        relink_asset_system, substitute_asset_folder_system, toggle_missing_assets_system,
+    },
+    prefabs::{
+        add_prefab_system, apply_prefab_overrides_system, create_prefab_system,
+        revert_to_prefab_system,
+    },
+    saving_loading::{
+        new_level_system, open_level_system, save_scene_as_system, save_scene_system,
+    },
+    sub_levels::{add_sub_level_system, open_sub_level_system},
+    ui::asset_browser::{
+        DraggedAsset, import_asset_system, refresh_asset_browser_system, spawn_asset_at_cursor,
+        toggle_asset_browser_system,
     },
-    helper_funcs::{deselect_entity, strip_assets_prefix},
-    input::HighlightedMesh,
-    saving_loading::save_scene_system,
 };
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericInputKey {
+    Digit(u8),
+    Minus,
+    DecimalPoint,
+    Backspace,
+}
 #[derive(Debug, Message)]
 pub enum EditorCommand {
-    OpenFile,
+    ToggleAssetBrowser,
+    RefreshAssetBrowser,
+    // Copy a glTF from anywhere on disk into the asset folders
+    ImportAsset,
+    // Place a glTF dropped from the asset browser under the cursor
+    SpawnAsset(DraggedAsset),
+    ToggleMissingAssets,
+    // Point the objects missing this asset path at a glTF picked on disk
+    RelinkAsset(String),
+    // Swap the start of missing asset paths for another folder
+    SubstituteAssetFolder { from: String, to: String },
     DeleteSelected,
+    Duplicate,
+    Copy,
+    Paste,
+    SelectAll,
     BeginTransform,
     SetMode(ManipulationMode),
     RestrictAxis(EditorAxis),
+    RestrictPlane(EditorAxis),
+    NumericInput(NumericInputKey),
     Confirm,
     Cancel,
     SwapLocal,
+    SwapPivot,
+    ToggleSnapping,
     Save,
+    SaveAs,
+    OpenLevel,
+    NewLevel,
+    // Save the active object's subtree as a prefab and replace it with an instance
+    CreatePrefab,
+    AddPrefab,
+    ApplyPrefabOverrides,
+    RevertToPrefab,
+    AddSubLevel,
+    // Open the file of the selected sub-level as the level being edited
+    OpenSubLevel,
+    Undo,
+    Redo,
 }
 pub fn editor_command_executor(
     mut cmd_reader: MessageReader<EditorCommand>,
     mut move_state: ResMut<MoveState>,
     mut commands: Commands,
     mut global_transforms: Query<&mut GlobalTransform>,
+    mut transforms: Query<&mut Transform>,
     cam_q: Query<Entity, With<EditorCamera>>,
     window_q: Query<&Window, With<PrimaryWindow>>,
     mut manip_mode: ResMut<CurrentObjectManipulationMode>,
-    highlighted_mesh_q: Query<Entity, With<HighlightedMesh>>,
     mut selected_entity: ResMut<EditorSelected>,
-    mut rotation_edit_state: ResMut<RotationEditState>,
-    mut gltf_instances: ResMut<EditorGltfInstances>,
-    asset_server: Res<AssetServer>,
-    mut config_store: ResMut<GizmoConfigStore>,
+    editor_entities_q: Query<Entity, With<IncludeInSave>>,
+    editor_childof_query: Query<&EditorChildOf>,
+    mut history: ResMut<EditorHistory>,
+    mut snap_settings: ResMut<SnapSettings>,
 ) {
     for cmd in cmd_reader.read() {
         match cmd {
-            EditorCommand::OpenFile => {
-                let Ok(cam_entity) = cam_q.single() else {
-                    return;
-                };
-                let Ok(cam_transform) = global_transforms.get(cam_entity) else {
-                    return;
-                };
-                open_file_dialog(
-                    &mut commands,
-                    &asset_server,
-                    cam_transform,
-                    &mut gltf_instances,
+            EditorCommand::ToggleAssetBrowser => {
+                commands.run_system_cached(toggle_asset_browser_system);
+            }
+            EditorCommand::RefreshAssetBrowser => {
+                commands.run_system_cached(refresh_asset_browser_system);
+            }
+            EditorCommand::ImportAsset => {
+                commands.run_system_cached(import_asset_system);
+            }
+            EditorCommand::SpawnAsset(asset) => {
+                if manip_mode.mode == ManipulationMode::None {
+                    commands.run_system_cached_with(spawn_asset_at_cursor, asset.clone());
+                }
+            }
+            EditorCommand::ToggleMissingAssets => {
+                commands.run_system_cached(toggle_missing_assets_system);
+            }
+            EditorCommand::RelinkAsset(asset_path) => {
+                commands.run_system_cached_with(relink_asset_system, asset_path.clone());
+            }
+            EditorCommand::SubstituteAssetFolder { from, to } => {
+                commands.run_system_cached_with(
+                    substitute_asset_folder_system,
+                    (from.clone(), to.clone()),
                 );
             }
             EditorCommand::DeleteSelected => {
-                if let Some(entity) = selected_entity.0 {
-                    commands.entity(entity).despawn();
-                    selected_entity.0 = None;
+                if !selected_entity.is_empty() {
+                    // An unconfirmed move, rotation or scale is dropped, so undoing the delete
+                    // brings the objects back where they were
+                    if manip_mode.mode != ManipulationMode::None {
+                        revert_transform(&mut transforms, &move_state);
+                        move_state.typed_input.clear();
+                    }
+                    let entities = top_level_selection(&selected_entity, &editor_childof_query);
+                    // Despawn through the world so the subtrees can be snapshotted for undo
+                    commands.queue(move |world: &mut World| {
+                        let deletions = entities
+                            .into_iter()
+                            .map(|entity| EditorEdit::Delete {
+                                root: entity,
+                                snapshot: despawn_subtree(world, entity),
+                            })
+                            .collect();
+                        world
+                            .resource_mut::<EditorHistory>()
+                            .push(EditorEdit::Group(deletions));
+                    });
+                    selected_entity.clear();
                     manip_mode.mode = ManipulationMode::None;
                 }
             }
+            EditorCommand::Duplicate => {
+                if !selected_entity.is_empty() && manip_mode.mode == ManipulationMode::None {
+                    let entities = top_level_selection(&selected_entity, &editor_childof_query);
+                    commands.queue(move |world: &mut World| {
+                        let copies: Vec<Entity> = entities
+                            .into_iter()
+                            .filter_map(|entity| duplicate_subtree(world, entity))
+                            .collect();
+                        if copies.is_empty() {
+                            return;
+                        }
+                        let spawns = copies
+                            .iter()
+                            .map(|copy| EditorEdit::Spawn {
+                                root: *copy,
+                                snapshot: Vec::new(),
+                            })
+                            .collect();
+                        world
+                            .resource_mut::<EditorHistory>()
+                            .push(EditorEdit::Group(spawns));
+                        // Grab the copies right away, they're picked up next frame once their
+                        // transforms have propagated
+                        world.resource_mut::<EditorSelected>().0 = copies;
+                        world.write_message(EditorCommand::SetMode(ManipulationMode::Move));
+                        world.write_message(EditorCommand::BeginTransform);
+                    });
+                }
+            }
+            EditorCommand::Copy => {
+                if !selected_entity.is_empty() {
+                    commands.run_system_cached(copy_selection_to_clipboard);
+                }
+            }
+            EditorCommand::Paste => {
+                if manip_mode.mode == ManipulationMode::None {
+                    commands.run_system_cached(paste_from_clipboard);
+                }
+            }
+            EditorCommand::SelectAll => {
+                if manip_mode.mode == ManipulationMode::None {
+                    selected_entity.0 = editor_entities_q.iter().collect();
+                }
+            }
             EditorCommand::SetMode(mode) => {
                 // Reset whatever transforming if we were in a different manipulation mode
                 // since it wasn't confirmed
                 if manip_mode.mode != ManipulationMode::None {
-                    revert_transform(selected_entity.0, &mut global_transforms, &mut move_state);
+                    revert_transform(&mut transforms, &move_state);
                 }
                 manip_mode.axis_restriction = None;
+                manip_mode.plane_restriction = None;
                 manip_mode.mode = *mode;
             }
             EditorCommand::Cancel => {
                 if manip_mode.mode != ManipulationMode::None {
-                    revert_transform(selected_entity.0, &mut global_transforms, &mut move_state);
+                    revert_transform(&mut transforms, &move_state);
+                    move_state.typed_input.clear();
                 } else {
-                    let (config, _) = config_store.config_mut::<DefaultGizmoConfigGroup>();
-                    deselect_entity(
-                        &mut selected_entity,
-                        &mut rotation_edit_state,
-                        highlighted_mesh_q,
-                        &mut commands,
-                        config,
-                    );
+                    selected_entity.clear();
                 }
 
                 manip_mode.mode = ManipulationMode::None;
                 manip_mode.axis_restriction = None;
+                manip_mode.plane_restriction = None;
             }
             EditorCommand::Confirm => {
                 if manip_mode.mode != ManipulationMode::None {
+                    let edits: Vec<EditorEdit> = move_state
+                        .selection_start
+                        .iter()
+                        .filter_map(|start| {
+                            let transform = transforms.get(start.entity).ok()?;
+                            (*transform != start.local).then(|| EditorEdit::Transform {
+                                entity: start.entity,
+                                before: start.local,
+                                after: *transform,
+                            })
+                        })
+                        .collect();
+                    if !edits.is_empty() {
+                        history.push(EditorEdit::Group(edits));
+                    }
+                    move_state.typed_input.clear();
                     manip_mode.mode = ManipulationMode::None;
                     manip_mode.axis_restriction = None;
+                    manip_mode.plane_restriction = None;
                 }
             }
             EditorCommand::BeginTransform => {
-                if let Some(entity) = selected_entity.0 {
-                    let Ok(obj_global_transform) = global_transforms.get(entity) else {
-                        continue;
-                    };
-
-                    let Ok(cam_entity) = cam_q.single() else {
-                        continue;
-                    };
-                    let Ok(cam_transform) = global_transforms.get(cam_entity) else {
-                        continue;
-                    };
-                    let Ok(window) = window_q.single() else {
-                        continue;
-                    };
-                    let Some(cursor_pos) = window.cursor_position() else {
-                        continue;
-                    };
+                let Some(active_entity) = selected_entity.active() else {
+                    continue;
+                };
+                let Ok(active_global_transform) = global_transforms.get(active_entity) else {
+                    continue;
+                };
+                let Ok(cam_entity) = cam_q.single() else {
+                    continue;
+                };
+                let Ok(cam_transform) = global_transforms.get(cam_entity) else {
+                    continue;
+                };
+                let Ok(window) = window_q.single() else {
+                    continue;
+                };
+                let Some(cursor_pos) = window.cursor_position() else {
+                    continue;
+                };
+                // Children of selected entities follow their parent, so only manipulate the roots
+                let selection_start: Vec<SelectionStartTransform> =
+                    top_level_selection(&selected_entity, &editor_childof_query)
+                        .into_iter()
+                        .filter_map(|entity| {
+                            Some(SelectionStartTransform {
+                                entity,
+                                global: *global_transforms.get(entity).ok()?,
+                                local: *transforms.get(entity).ok()?,
+                            })
+                        })
+                        .collect();
+                if selection_start.is_empty() {
+                    continue;
+                }
+                let pivot = match manip_mode.pivot {
+                    PivotPoint::MedianPoint => {
+                        selection_start
+                            .iter()
+                            .map(|start| start.global.translation())
+                            .sum::<Vec3>()
+                            / selection_start.len() as f32
+                    }
+                    PivotPoint::ActiveObject => active_global_transform.translation(),
+                };
+                let (scale, rotation, _) = active_global_transform.to_scale_rotation_translation();
 
-                    let depth = (obj_global_transform.translation() - cam_transform.translation())
-                        .dot(*cam_transform.forward());
+                let depth = (pivot - cam_transform.translation()).dot(*cam_transform.forward());
 
-                    move_state.start_cursor = cursor_pos;
-                    move_state.start_transform = *obj_global_transform;
-                    move_state.start_depth = depth;
-                }
+                move_state.start_cursor = cursor_pos;
+                move_state.start_transform = GlobalTransform::from(Transform {
+                    translation: pivot,
+                    rotation,
+                    scale,
+                });
+                move_state.start_depth = depth;
+                move_state.selection_start = selection_start;
+                move_state.typed_input.clear();
             }
             EditorCommand::RestrictAxis(axis) => {
-                revert_transform(selected_entity.0, &mut global_transforms, &mut move_state);
-                manip_mode.axis_restriction = Some(*axis)
+                revert_transform(&mut transforms, &move_state);
+                manip_mode.axis_restriction = Some(*axis);
+                manip_mode.plane_restriction = None;
+            }
+            EditorCommand::RestrictPlane(axis) => {
+                revert_transform(&mut transforms, &move_state);
+                manip_mode.plane_restriction = Some(*axis);
+                manip_mode.axis_restriction = None;
+                if !manip_mode.takes_typed_value() {
+                    move_state.typed_input.clear();
+                }
+            }
+            EditorCommand::NumericInput(key) => {
+                if manip_mode.mode != ManipulationMode::None && manip_mode.takes_typed_value() {
+                    edit_typed_input(&mut move_state.typed_input, *key);
+                }
             }
             EditorCommand::SwapLocal => {
                 manip_mode.local = !manip_mode.local;
             }
+            EditorCommand::SwapPivot => {
+                manip_mode.pivot = match manip_mode.pivot {
+                    PivotPoint::MedianPoint => PivotPoint::ActiveObject,
+                    PivotPoint::ActiveObject => PivotPoint::MedianPoint,
+                };
+            }
+            EditorCommand::ToggleSnapping => {
+                snap_settings.enabled = !snap_settings.enabled;
+            }
             EditorCommand::Save => {
-                dbg!("run");
                 commands.run_system_cached(save_scene_system);
             }
+            EditorCommand::SaveAs => {
+                commands.run_system_cached(save_scene_as_system);
+            }
+            EditorCommand::OpenLevel => {
+                commands.run_system_cached(open_level_system);
+            }
+            EditorCommand::NewLevel => {
+                commands.run_system_cached(new_level_system);
+            }
+            EditorCommand::CreatePrefab => {
+                if manip_mode.mode == ManipulationMode::None {
+                    commands.run_system_cached(create_prefab_system);
+                }
+            }
+            EditorCommand::AddPrefab => {
+                commands.run_system_cached(add_prefab_system);
+            }
+            EditorCommand::ApplyPrefabOverrides => {
+                if manip_mode.mode == ManipulationMode::None {
+                    commands.run_system_cached(apply_prefab_overrides_system);
+                }
+            }
+            EditorCommand::RevertToPrefab => {
+                if manip_mode.mode == ManipulationMode::None {
+                    commands.run_system_cached(revert_to_prefab_system);
+                }
+            }
+            EditorCommand::AddSubLevel => {
+                commands.run_system_cached(add_sub_level_system);
+            }
+            EditorCommand::OpenSubLevel => {
+                commands.run_system_cached(open_sub_level_system);
+            }
+            EditorCommand::Undo => {
+                commands.run_system_cached(undo_last_edit);
+            }
+            EditorCommand::Redo => {
+                commands.run_system_cached(redo_last_edit);
+            }
         }
     }
 }
-fn revert_transform(
-    selected: Option<Entity>,
-    transforms: &mut Query<&mut GlobalTransform>,
-    move_state: &mut MoveState,
-) {
-    if let Some(entity) = selected {
-        if let Ok(mut obj_transform) = transforms.get_mut(entity) {
-            *obj_transform = move_state.start_transform;
-            let mut local_trans = obj_transform.compute_transform();
-            local_trans.translation.x += 1000.0;
-            *obj_transform = GlobalTransform::from(local_trans);
+fn edit_typed_input(typed_input: &mut String, key: NumericInputKey) {
+    match key {
+        NumericInputKey::Digit(digit) => typed_input.push(char::from(b'0' + digit)),
+        // Minus flips the sign wherever the cursor is, like in Blender
+        NumericInputKey::Minus => {
+            if typed_input.starts_with('-') {
+                typed_input.remove(0);
+            } else {
+                typed_input.insert(0, '-');
+            }
+        }
+        NumericInputKey::DecimalPoint => {
+            if !typed_input.contains('.') {
+                typed_input.push('.');
+            }
+        }
+        NumericInputKey::Backspace => {
+            typed_input.pop();
         }
     }
 }
-pub fn open_file_dialog(
-    commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    cam_transform: &GlobalTransform,
-    gltf_instances: &mut ResMut<EditorGltfInstances>,
-) {
-    if let Some(path) = FileDialog::new().pick_file() {
-        dbg!(&path);
-        match strip_assets_prefix(&path) {
-            Some(relative_path) => {
-                println!("Relative path inside assets: {}", relative_path.display());
-                let gltf = asset_server.load(
-                    GltfAssetLabel::Scene(0)
-                        .from_asset(relative_path.to_string_lossy().to_string()),
-                );
-                let gltf_entity = commands
-                    .spawn((
-                        Name::new("test glb"),
-                        SceneRoot(gltf),
-                        *cam_transform,
-                        IncludeInSave,
-                    ))
-                    .id();
-                dbg!("spawned with includeinsave");
-                gltf_instances.0.push(InstantiatedGltfInstance {
-                    path: path.to_string_lossy().to_string(),
-                    entity: gltf_entity,
-                    parent: None,
-                });
-            }
-            None => {
-                eprintln!("Selected file is not inside the assets folder!");
-            }
+fn revert_transform(transforms: &mut Query<&mut Transform>, move_state: &MoveState) {
+    for start in &move_state.selection_start {
+        if let Ok(mut obj_transform) = transforms.get_mut(start.entity) {
+            *obj_transform = start.local;
         }
-    } else {
-        println!("No file selected");
     }
 }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetPath, UntypedAssetId},
+    gltf::{Gltf, GltfMesh, GltfNode},
+    prelude::*,
+};
+use common::level::AssetLabel;
+
+/// A node or mesh of a glTF, turned into a scene of its own once it has loaded, so it's
+/// spawned and picked like a whole glTF scene
+#[derive(Component, Clone)]
+pub enum GltfPart {
+    Node(Handle<GltfNode>),
+    Mesh(Handle<GltfMesh>),
+}
+/// The whole glTF a GltfRef points into, kept loaded to tell a missing file apart from a
+/// label the file doesn't have
+#[derive(Component)]
+pub struct GltfFile(pub Handle<Gltf>);
+pub enum GltfSource {
+    Scene(Handle<Scene>),
+    Part(GltfPart),
+}
+impl GltfSource {
+    pub fn insert_into(self, entity_commands: &mut EntityCommands) {
+        match self {
+            GltfSource::Scene(scene) => entity_commands.insert(SceneRoot(scene)),
+            GltfSource::Part(part) => entity_commands.insert(part),
+        };
+    }
+}
+// A scene, node or mesh of a glTF that can be placed, listed by the asset browser
+pub struct GltfEntry {
+    // Asset label to store in the GltfRef
+    pub label: String,
+    pub name: Option<String>,
+}
+
+/// Load what a GltfRef points at, the label is a glTF asset label like "Scene1", "Node3" or
+/// "Mesh0" and defaults to the first scene
+pub fn load_gltf_ref(
+    asset_server: &AssetServer,
+    asset_path: &str,
+    label: Option<&str>,
+) -> GltfSource {
+    let label = label.unwrap_or("Scene0");
+    let path = AssetPath::from(asset_path.to_owned()).with_label(label.to_owned());
+    match AssetLabel::parse(label) {
+        Some(AssetLabel::Node(_)) => GltfSource::Part(GltfPart::Node(asset_server.load(path))),
+        Some(AssetLabel::Mesh(_)) => GltfSource::Part(GltfPart::Mesh(asset_server.load(path))),
+        // Other labels are tried as scenes
+        Some(AssetLabel::Scene(_)) | None => GltfSource::Scene(asset_server.load(path)),
+    }
+}
+
+/// Every scene, named node and named mesh of a loaded glTF, scenes first
+pub fn gltf_entries(gltf: &Gltf) -> Vec<GltfEntry> {
+    let mut entries: Vec<GltfEntry> = gltf
+        .scenes
+        .iter()
+        .enumerate()
+        .map(|(index, scene)| GltfEntry {
+            label: GltfAssetLabel::Scene(index).to_string(),
+            name: gltf
+                .named_scenes
+                .iter()
+                .find(|(_, named)| *named == scene)
+                .map(|(name, _)| name.to_string()),
+        })
+        .collect();
+    let mut parts: Vec<GltfEntry> = gltf
+        .named_nodes
+        .iter()
+        .map(|(name, node)| (name, node.path()))
+        .chain(
+            gltf.named_meshes
+                .iter()
+                .map(|(name, mesh)| (name, mesh.path())),
+        )
+        .filter_map(|(name, path)| {
+            Some(GltfEntry {
+                label: path?.label()?.to_owned(),
+                name: Some(name.to_string()),
+            })
+        })
+        .collect();
+    parts.sort_by(|a, b| a.name.cmp(&b.name));
+    entries.extend(parts);
+    entries
+}
+
+// Build the scene of each node or mesh once its glTF has loaded, shared by every object
+// placing the same one. When the glTF is reloaded the scene is built again in place, which
+// respawns every instance of it.
+// The game builds them the same way in game/src/level.rs, along with spawn_gltf_node and
+// spawn_gltf_mesh. They can't be shared through common while the game is on another bevy
+// version, so fix both
+pub fn build_gltf_part_scenes(
+    parts_q: Query<(Entity, &GltfPart), Without<SceneRoot>>,
+    gltf_nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    mut node_events: MessageReader<AssetEvent<GltfNode>>,
+    mut mesh_events: MessageReader<AssetEvent<GltfMesh>>,
+    mut scenes: ResMut<Assets<Scene>>,
+    mut built: Local<HashMap<UntypedAssetId, (GltfPart, Handle<Scene>)>>,
+    mut commands: Commands,
+) {
+    let modified = node_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(id.untyped()),
+            _ => None,
+        })
+        .chain(mesh_events.read().filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(id.untyped()),
+            _ => None,
+        }));
+    for id in modified {
+        let Some((part, scene)) = built.get(&id) else {
+            continue;
+        };
+        if let Some(rebuilt) = part_scene(part, &gltf_nodes, &gltf_meshes) {
+            scenes.insert(scene, rebuilt).ok();
+        }
+    }
+
+    for (entity, part) in &parts_q {
+        let id = match part {
+            GltfPart::Node(node) => node.id().untyped(),
+            GltfPart::Mesh(mesh) => mesh.id().untyped(),
+        };
+        if let Some((_, scene)) = built.get(&id) {
+            commands.entity(entity).insert(SceneRoot(scene.clone()));
+            continue;
+        }
+        let Some(scene) = part_scene(part, &gltf_nodes, &gltf_meshes) else {
+            continue;
+        };
+        let scene = scenes.add(scene);
+        built.insert(id, (part.clone(), scene.clone()));
+        commands.entity(entity).insert(SceneRoot(scene));
+    }
+}
+// None until the node or mesh has loaded
+fn part_scene(
+    part: &GltfPart,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+) -> Option<Scene> {
+    let mut world = World::new();
+    match part {
+        GltfPart::Node(node) => {
+            let node = gltf_nodes.get(node)?;
+            // The object placing the node takes the place of its translation
+            let transform = Transform {
+                translation: Vec3::ZERO,
+                ..node.transform
+            };
+            spawn_gltf_node(&mut world, node, transform, gltf_nodes, gltf_meshes);
+        }
+        GltfPart::Mesh(mesh) => {
+            let mesh = gltf_meshes.get(mesh)?;
+            let root = world
+                .spawn((Transform::default(), Visibility::default()))
+                .id();
+            spawn_gltf_mesh(&mut world, mesh, root);
+        }
+    }
+    Some(Scene::new(world))
+}
+fn spawn_gltf_node(
+    world: &mut World,
+    node: &GltfNode,
+    transform: Transform,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+) -> Entity {
+    let entity = world
+        .spawn((
+            Name::new(node.name.clone()),
+            transform,
+            Visibility::default(),
+        ))
+        .id();
+    if let Some(mesh) = node.mesh.as_ref().and_then(|mesh| gltf_meshes.get(mesh)) {
+        spawn_gltf_mesh(world, mesh, entity);
+    }
+    for child in node
+        .children
+        .iter()
+        .filter_map(|child| gltf_nodes.get(child))
+    {
+        let child_entity = spawn_gltf_node(world, child, child.transform, gltf_nodes, gltf_meshes);
+        world.entity_mut(child_entity).insert(ChildOf(entity));
+    }
+    entity
+}
+// One entity per primitive, like the scenes the glTF loader spawns
+fn spawn_gltf_mesh(world: &mut World, mesh: &GltfMesh, parent: Entity) {
+    for primitive in &mesh.primitives {
+        let mut primitive_entity = world.spawn((
+            Name::new(primitive.name.clone()),
+            Mesh3d(primitive.mesh.clone()),
+            Transform::default(),
+            Visibility::default(),
+            ChildOf(parent),
+        ));
+        if let Some(material) = &primitive.material {
+            primitive_entity.insert(MeshMaterial3d(material.clone()));
+        }
+    }
+}
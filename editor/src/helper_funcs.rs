@@ -1,46 +1,94 @@
-use bevy::prelude::*;
-use std::path::{Path, PathBuf};
+use bevy::{camera::primitives::Aabb, math::Affine3A, prelude::*};
+use common::asset_roots::AssetRoots;
+use rfd::{MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    defs::{EditorSelected, RotationEditState},
-    input::HighlightedMesh,
+    const_defs::IMPORTED_ASSETS_FOLDER,
+    defs::{CurrentObjectManipulationMode, EditorAxis, EditorChildOf, EditorSelected},
 };
-pub fn deselect_entity(
-    selected_entity: &mut EditorSelected,
-    rotation_edit_state: &mut RotationEditState,
-    highlighted_mesh_q: Query<Entity, With<HighlightedMesh>>,
-    commands: &mut Commands,
-    config: &mut GizmoConfig,
-) {
-    config.enabled = false;
-    selected_entity.0 = None;
-    rotation_edit_state.initial_global_transform = None;
-    rotation_edit_state.initial_transform = None;
-    rotation_edit_state.rotation_edit_euler = None;
-    // delete the old highlight entities
-    for highlighted_mesh_entity in highlighted_mesh_q {
-        commands.entity(highlighted_mesh_entity).despawn();
-    }
-}
-pub fn strip_assets_prefix(path: &Path) -> Option<PathBuf> {
-    let mut found_assets = false;
-
-    let mut components = path.components();
-
-    // Skip components until we find "assets"
-    while let Some(c) = components.next() {
-        if c.as_os_str() == "assets" {
-            found_assets = true;
-            break;
-        }
+// Selected entities without a selected editor ancestor, the others just follow their parent
+pub fn top_level_selection(
+    selected_entity: &EditorSelected,
+    editor_childof_query: &Query<&EditorChildOf>,
+) -> Vec<Entity> {
+    selected_entity
+        .0
+        .iter()
+        .copied()
+        .filter(|entity| {
+            let mut current = *entity;
+            while let Ok(parent) = editor_childof_query.get(current) {
+                if selected_entity.contains(parent.0) {
+                    return false;
+                }
+                current = parent.0;
+            }
+            true
+        })
+        .collect()
+}
+/// The asset path to store for a picked file. Files outside every asset root can't be
+/// loaded, so offer to copy them into the default root instead
+pub fn asset_path_or_import(file: &Path, asset_roots: &AssetRoots) -> Option<String> {
+    if let Some(asset_path) = asset_roots.asset_path(file) {
+        return Some(asset_path);
     }
-
-    if found_assets {
-        // Collect the rest of the path components after "assets"
-        Some(components.collect())
-    } else {
-        None
+    let is_glb = file
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("glb"));
+    // A .gltf usually points at buffers and textures next to it, copying it alone breaks it
+    if !is_glb {
+        MessageDialog::new()
+            .set_level(MessageLevel::Error)
+            .set_title("File outside the asset folders")
+            .set_description(format!(
+                "{} is outside the asset folders. Move it, together with the files it uses, into {}",
+                file.display(),
+                asset_roots.default_root_path().display()
+            ))
+            .set_buttons(MessageButtons::Ok)
+            .show();
+        return None;
+    }
+    let import_folder = asset_roots.default_root_path().join(IMPORTED_ASSETS_FOLDER);
+    let copy_confirmed = MessageDialog::new()
+        .set_title("File outside the asset folders")
+        .set_description(format!(
+            "{} is outside the asset folders. Copy it to {}?",
+            file.display(),
+            import_folder.display()
+        ))
+        .set_buttons(MessageButtons::YesNo)
+        .show()
+        == MessageDialogResult::Yes;
+    if !copy_confirmed {
+        return None;
+    }
+    let destination = free_file_path(&import_folder, file)?;
+    let copied = fs::create_dir_all(&import_folder).and_then(|_| fs::copy(file, &destination));
+    if let Err(e) = copied {
+        eprintln!(
+            "Error copying {} to {}: {}",
+            file.display(),
+            destination.display(),
+            e
+        );
+        return None;
     }
+    asset_roots.asset_path(&destination)
+}
+// "door.glb", or "door_1.glb", "door_2.glb"... if that is taken
+fn free_file_path(folder: &Path, file: &Path) -> Option<PathBuf> {
+    let stem = file.file_stem()?.to_string_lossy();
+    let extension = file.extension()?.to_string_lossy();
+    std::iter::once(format!("{}.{}", stem, extension))
+        .chain((1..).map(|number| format!("{}_{}.{}", stem, number, extension)))
+        .map(|file_name| folder.join(file_name))
+        .find(|path| !path.exists())
 }
 // falloff = C / (scale + k) + min_value
 // where C and k control shape, min_value ensures minimal size increase
@@ -51,3 +99,64 @@ pub fn scale_increment_falloff(scale: f32) -> f32 {
 
     (c / (scale + k)).max(min_increment)
 }
+// The corners of a mesh's bounding box after `transform`, usually its GlobalTransform
+pub fn aabb_world_corners(aabb: &Aabb, transform: Affine3A) -> [Vec3; 8] {
+    let center = Vec3::from(aabb.center);
+    let half_extents = Vec3::from(aabb.half_extents);
+    std::array::from_fn(|corner_index| {
+        let sign = Vec3::new(
+            if corner_index & 1 == 0 { -1.0 } else { 1.0 },
+            if corner_index & 2 == 0 { -1.0 } else { 1.0 },
+            if corner_index & 4 == 0 { -1.0 } else { 1.0 },
+        );
+        transform.transform_point3(center + sign * half_extents)
+    })
+}
+// Round every component to the nearest multiple of step
+pub fn snap_to_step(value: Vec3, step: f32) -> Vec3 {
+    (value / step).round() * step
+}
+// Snap a move so the pivot lands on the world grid, or moves in whole steps along its local axes
+pub fn snap_movement_delta(
+    movement_delta: Vec3,
+    pivot: &GlobalTransform,
+    manip_mode: &CurrentObjectManipulationMode,
+    step: f32,
+) -> Vec3 {
+    if step <= 0.0 {
+        return movement_delta;
+    }
+    if manip_mode.local {
+        let rotation = pivot.rotation();
+        rotation * snap_to_step(rotation.inverse() * movement_delta, step)
+    } else {
+        let start = pivot.translation();
+        let snapped_delta = snap_to_step(start + movement_delta, step) - start;
+        // Only snap along the axis we're allowed to move on
+        match manip_mode.axis_restriction {
+            Some(EditorAxis::X) => Vec3::new(snapped_delta.x, 0.0, 0.0),
+            Some(EditorAxis::Y) => Vec3::new(0.0, snapped_delta.y, 0.0),
+            Some(EditorAxis::Z) => Vec3::new(0.0, 0.0, snapped_delta.z),
+            None => match manip_mode.plane_restriction {
+                Some(axis) => snapped_delta * (Vec3::ONE - axis_direction(axis)),
+                None => snapped_delta,
+            },
+        }
+    }
+}
+pub fn axis_direction(axis: EditorAxis) -> Vec3 {
+    match axis {
+        EditorAxis::X => Vec3::X,
+        EditorAxis::Y => Vec3::Y,
+        EditorAxis::Z => Vec3::Z,
+    }
+}
+// Snap the angle of a rotation to whole steps, keeping its axis
+pub fn snap_rotation(rotation: Quat, step_degrees: f32) -> Quat {
+    if step_degrees <= 0.0 {
+        return rotation;
+    }
+    let (axis, angle) = rotation.to_axis_angle();
+    let step = step_degrees.to_radians();
+    Quat::from_axis_angle(axis, (angle / step).round() * step)
+}
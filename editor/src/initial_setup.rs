@@ -12,10 +12,9 @@ use std::f32::consts::FRAC_PI_4;
 
 use crate::{
     camera::EditorCamera,
-    defs::{EditorChildOf, EditorEntityLink, EditorGltfInstances, EditorMaterials, GltfEntityRoot},
-    input::change_selected_entity,
+    defs::{EditorChildOf, EditorEntityLink, EditorMaterials, GltfEntityRoot},
+    input::{RefreshHighlights, change_selected_entity},
     outline_material::OutlineMaterial,
-    saving_loading::{load_gltf_instances, spawn_gltf_instances},
 };
 
 pub fn setup_scene(
@@ -24,7 +23,6 @@ pub fn setup_scene(
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut cursor_options: Single<&mut CursorOptions, With<Window>>,
-    gltf_instances_res: ResMut<EditorGltfInstances>,
     mut config_store: ResMut<GizmoConfigStore>,
 ) {
     let (config, _) = config_store.config_mut::<DefaultGizmoConfigGroup>();
@@ -70,8 +68,6 @@ pub fn setup_scene(
         .build(),
         Transform::from_rotation(Quat::from_euler(EulerRot::ZYX, 0.0, 0.0, -FRAC_PI_4)),
     ));
-    let instances = load_gltf_instances("test.ron");
-    spawn_gltf_instances(&mut commands, instances, gltf_instances_res, asset_server);
     //test glb
     //let test_glb = asset_server
     //    .load(GltfAssetLabel::Scene(0).from_asset("models/FlightHelmet/FlightHelmet.gltf"));
@@ -146,11 +142,14 @@ pub fn detect_gltf_children(
     mut outline_materials: ResMut<Assets<OutlineMaterial>>,
     entities_with_children: Query<(Entity, Option<&Mesh3d>, Option<&Children>)>,
     child_gltfs: Query<Entity, With<EditorChildOf>>,
+    mut refresh_highlights: ResMut<RefreshHighlights>,
 ) {
     let clicked_entity = trigger.entity;
     let Ok(editor_entity_link) = editor_entity_link_q.get(clicked_entity) else {
         return;
     };
+    // Also runs when a changed glTF is respawned, which replaces the meshes highlights show
+    refresh_highlights.0 = true;
     let white_matl = materials.add(Color::WHITE);
     let hover_matl = materials.add(Color::from(CYAN_300));
     let material = OutlineMaterial {
@@ -1,8 +1,10 @@
 use bevy::{
+    camera::primitives::Aabb,
     color::palettes::css::{BLUE, CRIMSON, GREEN, RED},
     input::mouse::{MouseMotion, MouseWheel},
     math::Affine3A,
     pbr::wireframe::Wireframe,
+    picking::mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings},
     prelude::*,
     window::PrimaryWindow,
 };
@@ -12,16 +14,22 @@ use crate::{
     camera::{EditorCamera, toggle_cursor_condition},
     defs::{
         CurrentObjectManipulationMode, EditorAxis, EditorChildOf, EditorMaterials, EditorSelected,
-        GltfEntityRoot, ManipulationMode, MoveState, RotationEditState,
+        GltfEntityRoot, ManipulationMode, MoveState, RotationEditState, SelectionStartTransform,
+        SnapSettings, SnapTarget, TransformGizmoState,
     },
-    execute_editor_commands::{EditorCommand, editor_command_executor},
-    helper_funcs::{deselect_entity, scale_increment_falloff},
+    execute_editor_commands::{EditorCommand, NumericInputKey, editor_command_executor},
+    helper_funcs::{
+        aabb_world_corners, axis_direction, scale_increment_falloff, snap_movement_delta,
+        snap_rotation,
+    },
+    marquee_selection::marquee_selection,
 };
 pub struct EditorInputPlugin;
 
 impl Plugin for EditorInputPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(EditorInput::default())
+            .insert_resource(RefreshHighlights::default())
             // Only check the hotkeys if we are not in freecam mode
             .add_message::<EditorCommand>()
             .add_systems(
@@ -29,7 +37,9 @@ impl Plugin for EditorInputPlugin {
                 resolve_hotkey_intents.run_if(toggle_cursor_condition),
             )
             .add_systems(Update, transform_using_manipulation_mode)
+            .add_systems(Update, update_selection_highlights)
             .add_systems(EguiPrimaryContextPass, editor_command_executor)
+            .add_systems(EguiPrimaryContextPass, marquee_selection)
             .add_systems(PreUpdate, store_mouse_deltas);
     }
 }
@@ -61,29 +71,80 @@ fn resolve_hotkey_intents(
     mut cmd_writer: MessageWriter<EditorCommand>,
     manip_mode: Res<CurrentObjectManipulationMode>,
     mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gizmo_state: Res<TransformGizmoState>,
     mut egui_ctx: EguiContexts,
 ) {
     let ctx = egui_ctx.ctx_mut().unwrap();
     if ctx.wants_keyboard_input() || ctx.wants_pointer_input() {
         return;
     }
-    if mouse_buttons.pressed(MouseButton::Left) {
+    // Gizmo drags confirm when the button is released instead
+    if mouse_buttons.pressed(MouseButton::Left) && gizmo_state.dragging.is_none() {
         cmd_writer.write(EditorCommand::Confirm);
     }
     if keyboard_input.just_pressed(KeyCode::KeyS) && keyboard_input.pressed(KeyCode::ControlLeft) {
-        cmd_writer.write(EditorCommand::Save);
+        if keyboard_input.pressed(KeyCode::ShiftLeft) {
+            cmd_writer.write(EditorCommand::SaveAs);
+        } else {
+            cmd_writer.write(EditorCommand::Save);
+        }
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyO) && keyboard_input.pressed(KeyCode::ControlLeft) {
+        cmd_writer.write(EditorCommand::OpenLevel);
         return;
     }
+    if keyboard_input.just_pressed(KeyCode::KeyN) && keyboard_input.pressed(KeyCode::ControlLeft) {
+        cmd_writer.write(EditorCommand::NewLevel);
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyZ) && keyboard_input.pressed(KeyCode::ControlLeft) {
+        // Don't undo underneath an unconfirmed transform, cancel it first
+        if manip_mode.mode != ManipulationMode::None {
+            cmd_writer.write(EditorCommand::Cancel);
+        }
+        if keyboard_input.pressed(KeyCode::ShiftLeft) {
+            cmd_writer.write(EditorCommand::Redo);
+        } else {
+            cmd_writer.write(EditorCommand::Undo);
+        }
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyA) && keyboard_input.pressed(KeyCode::ControlLeft) {
+        cmd_writer.write(EditorCommand::SelectAll);
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyC) && keyboard_input.pressed(KeyCode::ControlLeft) {
+        cmd_writer.write(EditorCommand::Copy);
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyV) && keyboard_input.pressed(KeyCode::ControlLeft) {
+        cmd_writer.write(EditorCommand::Paste);
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Tab) && keyboard_input.pressed(KeyCode::ShiftLeft) {
+        cmd_writer.write(EditorCommand::ToggleSnapping);
+    }
     if keyboard_input.just_pressed(KeyCode::KeyL) {
         cmd_writer.write(EditorCommand::SwapLocal);
     }
+    // Period types a decimal point while transforming
+    if keyboard_input.just_pressed(KeyCode::Period) && manip_mode.mode == ManipulationMode::None {
+        cmd_writer.write(EditorCommand::SwapPivot);
+    }
     if keyboard_input.just_pressed(KeyCode::KeyO) {
-        cmd_writer.write(EditorCommand::OpenFile);
+        cmd_writer.write(EditorCommand::ToggleAssetBrowser);
     }
-    if selected_entity.0.is_some() {
+    if !selected_entity.is_empty() {
         if keyboard_input.just_pressed(KeyCode::Delete) {
             cmd_writer.write(EditorCommand::DeleteSelected);
         }
+        if keyboard_input.just_pressed(KeyCode::KeyD)
+            && keyboard_input.pressed(KeyCode::ShiftLeft)
+            && manip_mode.mode == ManipulationMode::None
+        {
+            cmd_writer.write(EditorCommand::Duplicate);
+        }
         if keyboard_input.just_pressed(KeyCode::KeyG) {
             cmd_writer.write(EditorCommand::SetMode(ManipulationMode::Move));
             cmd_writer.write(EditorCommand::BeginTransform);
@@ -101,73 +162,146 @@ fn resolve_hotkey_intents(
         }
 
         if manip_mode.mode != ManipulationMode::None {
-            if keyboard_input.just_pressed(KeyCode::KeyX) {
-                cmd_writer.write(EditorCommand::RestrictAxis(EditorAxis::X));
+            // Shift excludes the axis instead, restricting to the other two
+            let axis_keys = [
+                (KeyCode::KeyX, EditorAxis::X),
+                (KeyCode::KeyY, EditorAxis::Y),
+                (KeyCode::KeyZ, EditorAxis::Z),
+            ];
+            for (key, axis) in axis_keys {
+                if keyboard_input.just_pressed(key) {
+                    if keyboard_input.pressed(KeyCode::ShiftLeft) {
+                        cmd_writer.write(EditorCommand::RestrictPlane(axis));
+                    } else {
+                        cmd_writer.write(EditorCommand::RestrictAxis(axis));
+                    }
+                }
             }
-            if keyboard_input.just_pressed(KeyCode::KeyY) {
-                cmd_writer.write(EditorCommand::RestrictAxis(EditorAxis::Y));
+            // Typing an exact offset/angle/factor
+            for key in keyboard_input.get_just_pressed() {
+                if let Some(numeric_key) = numeric_input_key(*key) {
+                    cmd_writer.write(EditorCommand::NumericInput(numeric_key));
+                }
             }
-            if keyboard_input.just_pressed(KeyCode::KeyZ) {
-                cmd_writer.write(EditorCommand::RestrictAxis(EditorAxis::Z));
+            if keyboard_input.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter]) {
+                cmd_writer.write(EditorCommand::Confirm);
             }
         }
     }
 }
+fn numeric_input_key(key: KeyCode) -> Option<NumericInputKey> {
+    let digit = match key {
+        KeyCode::Digit0 | KeyCode::Numpad0 => 0,
+        KeyCode::Digit1 | KeyCode::Numpad1 => 1,
+        KeyCode::Digit2 | KeyCode::Numpad2 => 2,
+        KeyCode::Digit3 | KeyCode::Numpad3 => 3,
+        KeyCode::Digit4 | KeyCode::Numpad4 => 4,
+        KeyCode::Digit5 | KeyCode::Numpad5 => 5,
+        KeyCode::Digit6 | KeyCode::Numpad6 => 6,
+        KeyCode::Digit7 | KeyCode::Numpad7 => 7,
+        KeyCode::Digit8 | KeyCode::Numpad8 => 8,
+        KeyCode::Digit9 | KeyCode::Numpad9 => 9,
+        KeyCode::Minus | KeyCode::NumpadSubtract => return Some(NumericInputKey::Minus),
+        KeyCode::Period | KeyCode::NumpadDecimal => return Some(NumericInputKey::DecimalPoint),
+        KeyCode::Backspace => return Some(NumericInputKey::Backspace),
+        _ => return None,
+    };
+    Some(NumericInputKey::Digit(digit))
+}
 
+// The selected editor entity this highlight was spawned for
 #[derive(Component)]
-pub struct HighlightedMesh;
+pub struct HighlightedMesh(pub Entity);
+// Set when glTF meshes were spawned or respawned, their highlights are made again from the
+// new meshes even if the selection didn't change
+#[derive(Resource, Default)]
+pub struct RefreshHighlights(pub bool);
 pub fn change_selected_entity(
     event: On<Pointer<Press>>,
     mut selected_entity: ResMut<EditorSelected>,
-    mut rotation_edit_state: ResMut<RotationEditState>,
-    highlighted_mesh_q: Query<Entity, With<HighlightedMesh>>,
     root_q: Query<&GltfEntityRoot>,
+    manip_mode: Res<CurrentObjectManipulationMode>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gizmo_state: Res<TransformGizmoState>,
+) {
+    // Don't select another object if we're trying to move/rotate an object already,
+    // or grabbing a gizmo handle in front of it
+    if manip_mode.mode != ManipulationMode::None || gizmo_state.hovered.is_some() {
+        return;
+    }
+    let entity = event.event_target();
+    let Ok(root_entity) = root_q.get(entity) else {
+        return;
+    };
+    // Shift-click adds to/removes from the selection, a plain click replaces it
+    if keyboard_input.pressed(KeyCode::ShiftLeft) {
+        selected_entity.toggle(root_entity.0);
+    } else {
+        selected_entity.select_only(root_entity.0);
+    }
+}
+// Keep one set of highlight meshes per selected entity, whatever changed the selection
+pub fn update_selection_highlights(
+    selected_entity: Res<EditorSelected>,
+    mut rotation_edit_state: ResMut<RotationEditState>,
+    highlighted_mesh_q: Query<(Entity, &HighlightedMesh)>,
     entities_with_children: Query<
         (&GlobalTransform, Option<&Mesh3d>, Option<&Children>),
         Without<HighlightedMesh>,
     >,
-    editor_materials: Res<EditorMaterials>,
-    manip_mode: Res<CurrentObjectManipulationMode>,
-    meshes: Res<Assets<Mesh>>,
+    editor_materials: Option<Res<EditorMaterials>>,
     editor_childof_query: Query<&EditorChildOf>,
     mut commands: Commands,
     mut config_store: ResMut<GizmoConfigStore>,
+    mut refresh_highlights: ResMut<RefreshHighlights>,
+    // The UI takes the selection mutably every frame, so compare instead of using change detection
+    mut previous_selection: Local<Vec<Entity>>,
 ) {
-    let (config, _) = config_store.config_mut::<DefaultGizmoConfigGroup>();
-    dbg!("pressed");
-    // Don't select another object if we're trying to move/rotate an object already
-    if manip_mode.mode != ManipulationMode::None {
+    let refresh = std::mem::take(&mut refresh_highlights.0);
+    if *previous_selection == selected_entity.0 && !refresh {
         return;
     }
-    // Don't allow selecting the highlights...
-    if highlighted_mesh_q.get(event.entity).is_ok() {
-        dbg!("selected highlight");
-    }
-    deselect_entity(
-        &mut selected_entity,
-        &mut rotation_edit_state,
-        highlighted_mesh_q,
-        &mut commands,
-        config,
-    );
-    config.enabled = true;
-    let entity = event.event_target();
+    if *previous_selection != selected_entity.0 {
+        *previous_selection = selected_entity.0.clone();
+        let (config, _) = config_store.config_mut::<DefaultGizmoConfigGroup>();
+        config.enabled = !selected_entity.is_empty();
+        // The inspector re-reads the active entity's rotation
+        rotation_edit_state.initial_global_transform = None;
+        rotation_edit_state.initial_transform = None;
+        rotation_edit_state.rotation_edit_euler = None;
+    }
 
+    // delete the highlights of entities that aren't selected anymore, or all of them when
+    // the meshes they were made from changed
+    let mut already_highlighted = Vec::new();
+    for (highlight_entity, highlighted_mesh) in highlighted_mesh_q {
+        if selected_entity.contains(highlighted_mesh.0) && !refresh {
+            already_highlighted.push(highlighted_mesh.0);
+        } else {
+            commands.entity(highlight_entity).despawn();
+        }
+    }
+    // The materials are created once the first glTF finished loading
+    let Some(editor_materials) = editor_materials else {
+        return;
+    };
     // Add new highlight entities to each mesh component
-    if let Ok(root_entity) = root_q.get(entity) {
-        if let Ok((root_global_transform, _, _)) = entities_with_children.get(root_entity.0) {
+    for selected in selected_entity.0.iter().copied() {
+        if already_highlighted.contains(&selected) {
+            continue;
+        }
+        if let Ok((root_global_transform, _, _)) = entities_with_children.get(selected) {
             let root_inverse = root_global_transform.affine().inverse();
             spawn_highlight_for_mesh_recursive(
                 &entities_with_children,
-                root_entity.0,
+                selected,
                 &mut commands,
-                &meshes,
                 &editor_materials,
                 editor_childof_query,
-                root_entity.0,
+                selected,
+                selected,
                 root_inverse,
             );
-            selected_entity.0 = Some(root_entity.0);
         }
     }
 }
@@ -179,9 +313,9 @@ fn spawn_highlight_for_mesh_recursive(
     >,
     current_entity: Entity,
     commands: &mut Commands,
-    meshes: &Res<Assets<Mesh>>,
     editor_materials: &EditorMaterials,
     editor_childof_query: Query<&EditorChildOf>,
+    selected: Entity,
     mut root_entity: Entity,
     mut root_inverse: Affine3A,
 ) {
@@ -207,7 +341,7 @@ fn spawn_highlight_for_mesh_recursive(
                     new_transform,
                     Mesh3d(mesh.0.clone()),
                     MeshMaterial3d(editor_materials.pressed_matl.clone()),
-                    HighlightedMesh,
+                    HighlightedMesh(selected),
                 ))
                 .id();
             commands.entity(highlight_entity).insert(Wireframe);
@@ -229,9 +363,9 @@ fn spawn_highlight_for_mesh_recursive(
                     entities_with_children,
                     child,
                     commands,
-                    meshes,
                     editor_materials,
                     editor_childof_query,
+                    selected,
                     root_entity,
                     root_inverse,
                 );
@@ -241,17 +375,25 @@ fn spawn_highlight_for_mesh_recursive(
 }
 fn transform_using_manipulation_mode(
     window_q: Query<&Window, With<PrimaryWindow>>,
-    cam_q: Query<(Entity, &Camera), With<EditorCamera>>,
-    mut transforms: Query<(&GlobalTransform, &mut Transform)>,
+    cam_q: Query<(&GlobalTransform, &Camera), With<EditorCamera>>,
+    mut transforms: Query<&mut Transform>,
     move_state: Res<MoveState>,
     selected_entity: Res<EditorSelected>,
     manip_mode: Res<CurrentObjectManipulationMode>,
     mut rotation_edit_state: ResMut<RotationEditState>,
+    snap_settings: Res<SnapSettings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut mesh_ray_cast: MeshRayCast,
+    parents_q: Query<&ChildOf>,
+    children_q: Query<&Children>,
+    global_transforms: Query<&GlobalTransform>,
+    mesh_bounds_q: Query<(&Aabb, &GlobalTransform), (With<Mesh3d>, Without<HighlightedMesh>)>,
 ) {
     if manip_mode.mode == ManipulationMode::None {
         return;
     }
-    dbg!(manip_mode.mode);
+    // Holding Ctrl flips the persistent snapping toggle for this manipulation
+    let snapping = snap_settings.enabled != keyboard_input.pressed(KeyCode::ControlLeft);
     let Ok(window) = window_q.single() else {
         return;
     };
@@ -259,88 +401,303 @@ fn transform_using_manipulation_mode(
         return;
     };
 
-    let Ok((cam_entity, camera)) = cam_q.single() else {
-        return;
-    };
-    let Ok((cam_transform, _cam_local_transform)) = transforms.get(cam_entity) else {
-        return;
-    };
-    let cam_transform = cam_transform.clone();
-    let Some(entity) = selected_entity.0 else {
-        return;
-    };
-    let Ok((global_transform, mut transform)) = transforms.get_mut(entity) else {
+    let Ok((cam_transform, camera)) = cam_q.single() else {
         return;
     };
-    let Ok(current_ray) = camera.viewport_to_world(&cam_transform, cursor_position) else {
+    let Ok(current_ray) = camera.viewport_to_world(cam_transform, cursor_position) else {
         return;
     };
+    let pivot = move_state.start_transform.translation();
+    let pivot_rotation = move_state.start_transform.rotation();
+    // A typed value replaces the mouse input (and snapping) entirely
+    let typed_value = move_state
+        .typed_value()
+        .filter(|_| manip_mode.takes_typed_value());
+    // Work out the change once for the pivot, then apply it to every manipulated entity
     match manip_mode.mode {
-        ManipulationMode::Move => move_object(
-            global_transform,
-            &mut transform,
-            &move_state,
-            &cam_transform,
-            current_ray,
-            camera,
-            &manip_mode,
-        ),
+        ManipulationMode::Move => {
+            // Surface/vertex snapping only places free moves, axis restricted moves use the grid
+            if typed_value.is_none()
+                && snapping
+                && snap_settings.target != SnapTarget::Increment
+                && manip_mode.axis_restriction.is_none()
+                && manip_mode.plane_restriction.is_none()
+            {
+                if let Some((point, normal)) = surface_snap_point(
+                    &mut mesh_ray_cast,
+                    current_ray,
+                    &move_state,
+                    &parents_q,
+                    snap_settings.target,
+                ) {
+                    // Turn the active object's up axis towards the surface
+                    let start_up = if snap_settings.align_to_normal {
+                        (pivot_rotation * Vec3::Y).normalize()
+                    } else {
+                        Vec3::Y
+                    };
+                    let up = if snap_settings.align_to_normal {
+                        normal
+                    } else {
+                        Vec3::Y
+                    };
+                    let alignment = Quat::from_rotation_arc(start_up, up);
+                    let bottom_offset = if snap_settings.snap_bounds_bottom {
+                        selection_bottom_offset(
+                            &move_state,
+                            start_up,
+                            &children_q,
+                            &global_transforms,
+                            &mesh_bounds_q,
+                        )
+                    } else {
+                        0.0
+                    };
+                    let target_pivot = point + up * bottom_offset;
+                    for start in &move_state.selection_start {
+                        let Ok(mut transform) = transforms.get_mut(start.entity) else {
+                            continue;
+                        };
+                        let start_global = start.global.compute_transform();
+                        let new_global = Transform {
+                            translation: target_pivot
+                                + alignment * (start_global.translation - pivot),
+                            rotation: alignment * start_global.rotation,
+                            scale: start_global.scale,
+                        };
+                        let new_local = global_to_local(start, new_global);
+                        transform.translation = new_local.translation;
+                        transform.rotation = new_local.rotation;
+                    }
+                    return;
+                }
+                // Nothing under the cursor, fall back to moving on the view plane
+            }
+            let movement_delta = match typed_value {
+                Some(distance) => manipulation_axis(&move_state, &manip_mode) * distance,
+                None => {
+                    let Some(movement_delta) =
+                        move_object(&move_state, cam_transform, current_ray, camera, &manip_mode)
+                    else {
+                        return;
+                    };
+                    if snapping {
+                        snap_movement_delta(
+                            movement_delta,
+                            &move_state.start_transform,
+                            &manip_mode,
+                            snap_settings.translation_step,
+                        )
+                    } else {
+                        movement_delta
+                    }
+                }
+            };
+            for start in &move_state.selection_start {
+                let Ok(mut transform) = transforms.get_mut(start.entity) else {
+                    continue;
+                };
+                let start_global = start.global.compute_transform();
+                let new_global =
+                    start_global.with_translation(start_global.translation + movement_delta);
+                let new_local = global_to_local(start, new_global);
+                transform.translation = new_local.translation;
+                // Undo any alignment from surface snapping earlier in this manipulation
+                transform.rotation = new_local.rotation;
+            }
+        }
         ManipulationMode::Rotate => {
-            rotate_object(
-                &mut transform,
-                global_transform,
-                &move_state,
-                &cam_transform,
-                window,
-                cursor_position,
-                &manip_mode,
-                &mut rotation_edit_state,
-            );
+            let rotation = match typed_value {
+                Some(degrees) => {
+                    // Without an axis, rotate around the view axis like the trackball does
+                    let axis = if manip_mode.axis_restriction.is_some() {
+                        manipulation_axis(&move_state, &manip_mode)
+                    } else {
+                        *cam_transform.back()
+                    };
+                    Quat::from_axis_angle(axis, degrees.to_radians())
+                }
+                None => {
+                    let Some(rotation) = rotate_object(
+                        &move_state,
+                        cam_transform,
+                        window,
+                        cursor_position,
+                        &manip_mode,
+                    ) else {
+                        return;
+                    };
+                    if snapping {
+                        snap_rotation(rotation, snap_settings.rotation_step_degrees)
+                    } else {
+                        rotation
+                    }
+                }
+            };
+            for start in &move_state.selection_start {
+                let Ok(mut transform) = transforms.get_mut(start.entity) else {
+                    continue;
+                };
+                let start_global = start.global.compute_transform();
+                // Orbit around the pivot as well as turning in place
+                let new_global = Transform {
+                    translation: pivot + rotation * (start_global.translation - pivot),
+                    rotation: rotation * start_global.rotation,
+                    scale: start_global.scale,
+                };
+                let new_local = global_to_local(start, new_global);
+                transform.translation = new_local.translation;
+                transform.rotation = new_local.rotation;
+                if selected_entity.active() == Some(start.entity) {
+                    let (pitch, yaw, roll) = transform.rotation.to_euler(EulerRot::XYZ);
+                    rotation_edit_state.rotation_edit_euler =
+                        Some((pitch.to_degrees(), yaw.to_degrees(), roll.to_degrees()));
+                    rotation_edit_state.initial_global_transform = Some(start.global);
+                    rotation_edit_state.initial_transform = Some(*transform);
+                }
+            }
         }
         ManipulationMode::Scale => {
-            scale_object(
-                &mut transform,
-                &move_state,
-                &cam_transform,
-                current_ray,
-                camera,
-                &manip_mode,
-            );
+            let scale_factor = match typed_value {
+                Some(factor) => axis_scale_factor(factor.max(0.01), &manip_mode),
+                None => {
+                    let snap_step = snapping.then_some(snap_settings.scale_step);
+                    let Some(scale_factor) = scale_object(
+                        &move_state,
+                        cam_transform,
+                        current_ray,
+                        camera,
+                        &manip_mode,
+                        snap_step,
+                    ) else {
+                        return;
+                    };
+                    scale_factor
+                }
+            };
+            for start in &move_state.selection_start {
+                let Ok(mut transform) = transforms.get_mut(start.entity) else {
+                    continue;
+                };
+                let start_global = start.global.compute_transform();
+                // Spread the entities apart/together from the pivot, in its frame when local
+                let offset = start_global.translation - pivot;
+                let scaled_offset = if manip_mode.local {
+                    pivot_rotation * (scale_factor * (pivot_rotation.inverse() * offset))
+                } else {
+                    scale_factor * offset
+                };
+                let new_global = start_global.with_translation(pivot + scaled_offset);
+                transform.translation = global_to_local(start, new_global).translation;
+                // Apply relative to starting scale
+                transform.scale = start.local.scale * scale_factor;
+            }
         }
         ManipulationMode::None => {}
     }
 }
+// Cast the cursor ray against every mesh except the ones being moved.
+// Returns the point to snap to and the surface normal there
+fn surface_snap_point(
+    mesh_ray_cast: &mut MeshRayCast,
+    ray: Ray3d,
+    move_state: &MoveState,
+    parents_q: &Query<&ChildOf>,
+    target: SnapTarget,
+) -> Option<(Vec3, Vec3)> {
+    let moved: Vec<Entity> = move_state
+        .selection_start
+        .iter()
+        .map(|start| start.entity)
+        .collect();
+    // Highlights are children of the selection too, so this skips them as well
+    let filter = |entity: Entity| {
+        !moved.contains(&entity)
+            && !parents_q
+                .iter_ancestors(entity)
+                .any(|ancestor| moved.contains(&ancestor))
+    };
+    let settings = MeshRayCastSettings::default()
+        .with_filter(&filter)
+        .always_early_exit();
+    let (_, hit) = mesh_ray_cast.cast_ray(ray, &settings).first()?;
+    let normal = hit.normal.normalize_or(Vec3::Y);
+    let point = match target {
+        SnapTarget::Vertex => {
+            let triangle = hit.triangle?;
+            triangle.into_iter().min_by(|a, b| {
+                a.distance_squared(hit.point)
+                    .total_cmp(&b.distance_squared(hit.point))
+            })?
+        }
+        _ => hit.point,
+    };
+    Some((point, normal))
+}
+// Distance along `up` from the pivot down to the lowest corner of the selection's meshes,
+// measured where they were when the manipulation started
+fn selection_bottom_offset(
+    move_state: &MoveState,
+    up: Vec3,
+    children_q: &Query<&Children>,
+    global_transforms: &Query<&GlobalTransform>,
+    mesh_bounds_q: &Query<(&Aabb, &GlobalTransform), (With<Mesh3d>, Without<HighlightedMesh>)>,
+) -> f32 {
+    let pivot = move_state.start_transform.translation();
+    let mut offset: f32 = 0.0;
+    for start in &move_state.selection_start {
+        let Ok(current_global) = global_transforms.get(start.entity) else {
+            continue;
+        };
+        // The meshes already moved with the entity, so bring them back to its start transform
+        let to_start = start.global.affine() * current_global.affine().inverse();
+        for descendant in children_q.iter_descendants(start.entity) {
+            let Ok((aabb, mesh_transform)) = mesh_bounds_q.get(descendant) else {
+                continue;
+            };
+            for corner in aabb_world_corners(aabb, to_start * mesh_transform.affine()) {
+                offset = offset.max((pivot - corner).dot(up));
+            }
+        }
+    }
+    offset
+}
+// Convert a desired global transform into the entity's local space, using the parent
+// transform from when the manipulation started
+fn global_to_local(start: &SelectionStartTransform, new_global: Transform) -> Transform {
+    let parent_affine = start.global.affine() * start.local.compute_affine().inverse();
+    let local_affine = parent_affine.inverse() * new_global.compute_affine();
+    let (scale, rotation, translation) = local_affine.to_scale_rotation_translation();
+    Transform {
+        translation,
+        rotation,
+        scale,
+    }
+}
+// Returns the world space movement of the selection since the manipulation started
 fn move_object(
-    global_transform: &GlobalTransform,
-    transform: &mut Transform,
     move_state: &MoveState,
     cam_transform: &GlobalTransform,
     current_ray: Ray3d,
     camera: &Camera,
     manip_mode: &CurrentObjectManipulationMode,
-) {
-    let child_local = transform.clone();
-    let child_global = global_transform;
-    let child_affine = child_local.compute_affine();
-    let global_affine = child_global.affine();
-    let parent_affine = global_affine * child_affine.inverse();
-
+) -> Option<Vec3> {
     let plane_origin = move_state.start_transform.translation();
-    let plane_normal = cam_transform.forward();
-
-    let Ok(start_ray) = camera.viewport_to_world(cam_transform, move_state.start_cursor) else {
-        return;
-    };
-    let Some(start_world_pos) = intersect_ray_with_plane(start_ray, plane_origin, *plane_normal)
-    else {
-        return;
-    };
-    let Some(current_world_pos) =
-        intersect_ray_with_plane(current_ray, plane_origin, *plane_normal)
-    else {
-        return;
+    // Moving in a plane follows the cursor on that plane, otherwise on the view plane
+    let plane_normal = match manip_mode.plane_restriction {
+        Some(axis) if manip_mode.local => {
+            move_state.start_transform.rotation() * axis_direction(axis)
+        }
+        Some(axis) => axis_direction(axis),
+        None => *cam_transform.forward(),
     };
 
+    let start_ray = camera
+        .viewport_to_world(cam_transform, move_state.start_cursor)
+        .ok()?;
+    let start_world_pos = intersect_ray_with_plane(start_ray, plane_origin, plane_normal)?;
+    let current_world_pos = intersect_ray_with_plane(current_ray, plane_origin, plane_normal)?;
+
     let mut movement_delta = current_world_pos - start_world_pos;
     if let Some(axis) = manip_mode.axis_restriction {
         // Translate in either local or global space
@@ -364,29 +721,16 @@ fn move_object(
             };
         }
     }
-    // Desired new global translation
-    let desired_global_translation = move_state.start_transform.translation() + movement_delta;
-
-    // Convert desired global translation into local space relative to parent
-    // Construct a translation matrix for desired global position
-    let desired_global_affine = Mat4::from_translation(desired_global_translation);
-
-    // Calculate local affine by removing parent's transform
-    let local_affine = parent_affine.inverse() * desired_global_affine;
-
-    // Extract translation from local affine and set it as new local translation
-    transform.translation = local_affine.to_scale_rotation_translation().2;
+    Some(movement_delta)
 }
+// Returns the world space rotation to apply around the pivot
 fn rotate_object(
-    transform: &mut Transform,
-    global_transform: &GlobalTransform,
     move_state: &MoveState,
     cam_transform: &GlobalTransform,
     window: &Window,
     current_cursor: Vec2,
     manipulation_mode: &CurrentObjectManipulationMode,
-    rotation_edit_state: &mut RotationEditState,
-) {
+) -> Option<Quat> {
     let window_size = Vec2::new(window.width(), window.height());
     // If no axis lock, do full trackball rotation
     if manipulation_mode.axis_restriction.is_none() {
@@ -419,112 +763,85 @@ fn rotate_object(
         // Axis of rotation is the cross product of the two vectors
         let axis = v0.cross(v1);
         if axis.length_squared() < 1e-6 {
-            return; // No meaningful rotation
+            return None; // No meaningful rotation
         }
 
         let angle = v0.angle_between(v1);
         let world_axis = cam_transform.rotation() * axis;
 
-        let rotation = Quat::from_axis_angle(world_axis, angle);
-        transform.rotation = rotation * move_state.start_transform.rotation();
+        Some(Quat::from_axis_angle(world_axis.normalize(), angle))
     } else {
         // Axis lock rotation
         // Extract the locked axis in world space or local space
-        let locked_axis = match manipulation_mode.axis_restriction.unwrap() {
-            EditorAxis::X => {
-                if manipulation_mode.local {
-                    move_state.start_transform.rotation() * Vec3::X
-                } else {
-                    Vec3::X
-                }
-            }
-            EditorAxis::Y => {
-                if manipulation_mode.local {
-                    move_state.start_transform.rotation() * Vec3::Y
-                } else {
-                    Vec3::Y
-                }
-            }
-            EditorAxis::Z => {
-                if manipulation_mode.local {
-                    move_state.start_transform.rotation() * Vec3::Z
-                } else {
-                    Vec3::Z
-                }
-            }
-        };
+        let locked_axis = manipulation_axis(move_state, manipulation_mode);
         // Use horizontal cursor delta as rotation input
         let cursor_delta = current_cursor - move_state.start_cursor;
         let rotation_speed = 0.01;
         let angle = cursor_delta.x * rotation_speed;
 
         // Build rotation quaternion around locked axis
-        let rotation = Quat::from_axis_angle(locked_axis, angle);
-
-        // Apply rotation relative to original rotation
-        transform.rotation = rotation * move_state.start_transform.rotation();
-    }
-    let (pitch, yaw, roll) = transform.rotation.to_euler(EulerRot::XYZ);
-    rotation_edit_state.rotation_edit_euler =
-        Some((pitch.to_degrees(), yaw.to_degrees(), roll.to_degrees()));
-    rotation_edit_state.initial_global_transform = Some(global_transform.clone());
-    rotation_edit_state.initial_transform = Some(transform.clone());
+        Some(Quat::from_axis_angle(locked_axis, angle))
+    }
 }
+// Returns the per-axis scale factor relative to the starting scale
 fn scale_object(
-    transform: &mut Transform,
     move_state: &MoveState,
     cam_transform: &GlobalTransform,
     current_ray: Ray3d,
     camera: &Camera,
     manip_mode: &CurrentObjectManipulationMode,
-) {
+    snap_step: Option<f32>,
+) -> Option<Vec3> {
     let plane_origin = move_state.start_transform.translation();
     let plane_normal = cam_transform.forward();
 
-    let Ok(start_ray) = camera.viewport_to_world(cam_transform, move_state.start_cursor) else {
-        return;
-    };
-    let Some(start_world_pos) = intersect_ray_with_plane(start_ray, plane_origin, *plane_normal)
-    else {
-        return;
-    };
-    let Some(current_world_pos) =
-        intersect_ray_with_plane(current_ray, plane_origin, *plane_normal)
-    else {
-        return;
-    };
+    let start_ray = camera
+        .viewport_to_world(cam_transform, move_state.start_cursor)
+        .ok()?;
+    let start_world_pos = intersect_ray_with_plane(start_ray, plane_origin, *plane_normal)?;
+    let current_world_pos = intersect_ray_with_plane(current_ray, plane_origin, *plane_normal)?;
 
-    // Distances from object center (plane_origin)
+    // Distances from the pivot (plane_origin)
     let start_dist = (start_world_pos - plane_origin).length();
     let current_dist = (current_world_pos - plane_origin).length();
 
     if start_dist == 0.0 {
-        return; // avoid division by zero
+        return None; // avoid division by zero
     }
 
     // Calculate uniform scale factor: ratio of current distance to start distance
     let mut scale_factor = current_dist / start_dist;
 
+    // Snap before restricting to an axis so the untouched axes stay at exactly 1.0
+    if let Some(step) = snap_step.filter(|step| *step > 0.0) {
+        scale_factor = (scale_factor / step).round() * step;
+    }
+
     // Clamp scale factor to avoid zero or negative scaling
     scale_factor = scale_factor.max(0.01);
 
-    // Prepare final scale vector
-    let final_scale;
-
+    Some(axis_scale_factor(scale_factor, manip_mode))
+}
+fn axis_scale_factor(scale_factor: f32, manip_mode: &CurrentObjectManipulationMode) -> Vec3 {
     if let Some(axis) = manip_mode.axis_restriction {
         // Scale only along locked axis, keep others 1.0
-        final_scale = match axis {
-            EditorAxis::X => Vec3::new(scale_factor, 1.0, 1.0),
-            EditorAxis::Y => Vec3::new(1.0, scale_factor, 1.0),
-            EditorAxis::Z => Vec3::new(1.0, 1.0, scale_factor),
-        };
+        Vec3::ONE + axis_direction(axis) * (scale_factor - 1.0)
+    } else if let Some(axis) = manip_mode.plane_restriction {
+        // Scale along the other two axes, keep the excluded one 1.0
+        Vec3::splat(scale_factor) - axis_direction(axis) * (scale_factor - 1.0)
     } else {
         // Uniform scale on all axes
-        final_scale = Vec3::splat(scale_factor);
+        Vec3::splat(scale_factor)
+    }
+}
+// The restricted axis (X if there is none) in world space or in the pivot's local space
+fn manipulation_axis(move_state: &MoveState, manip_mode: &CurrentObjectManipulationMode) -> Vec3 {
+    let axis = axis_direction(manip_mode.axis_restriction.unwrap_or(EditorAxis::X));
+    if manip_mode.local {
+        move_state.start_transform.rotation() * axis
+    } else {
+        axis
     }
-
-    // Apply relative to starting scale
-    transform.scale = move_state.start_transform.scale() * final_scale;
 }
 fn intersect_ray_with_plane(ray: Ray3d, plane_origin: Vec3, plane_normal: Vec3) -> Option<Vec3> {
     let denom = ray.direction.dot(plane_normal);
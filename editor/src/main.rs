@@ -1,6 +1,7 @@
 use std::f32::consts::*;
 
 use bevy::{
+    asset::io::{AssetSourceBuilder, file::FileAssetReader},
     core_pipeline::prepass::{DeferredPrepass, DepthPrepass, MotionVectorPrepass, NormalPrepass},
     light::DirectionalLightShadowMap,
     pbr::{DefaultOpaqueRendererMethod, wireframe::WireframePlugin},
@@ -8,44 +9,82 @@ use bevy::{
     render::RenderDebugFlags,
     window::{CursorGrabMode, CursorOptions},
 };
+mod autosave;
 mod camera;
+mod clipboard;
 mod const_defs;
 mod defs;
+mod editor_history;
 mod events;
 mod execute_editor_commands;
+mod gltf_parts;
 mod helper_funcs;
 mod initial_setup;
 mod input;
 mod level;
+mod marquee_selection;
+mod missing_assets;
 mod outline_material;
+mod prefabs;
 mod saving_loading;
+mod sub_levels;
+#[cfg(test)]
+mod test_helpers;
+mod thumbnails;
+mod transform_gizmo;
 mod ui;
+use autosave::AutosavePlugin;
 use camera::CameraPlugin;
+use common::{Interactable, Player, asset_roots::AssetRoots};
 use initial_setup::setup_scene;
 
 use crate::{
     defs::{
-        CurrentObjectManipulationMode, EditorGltfInstances, EditorSelected, MoveState,
-        RotationEditState,
+        CurrentLevel, CurrentObjectManipulationMode, EditorAssetRoots, EditorSelected,
+        EditorStatus, ManipulationMode, MoveState, RotationEditState, SnapSettings,
     },
+    editor_history::EditorHistory,
+    gltf_parts::build_gltf_part_scenes,
     initial_setup::detect_gltf_children,
     input::EditorInputPlugin,
+    missing_assets::MissingAssetsPlugin,
     outline_material::OutlineMaterial,
-    saving_loading::{finish_loading_scene, load_scene_system, save_scene_system},
+    saving_loading::{
+        finish_loading_scene, open_level_from_args, reload_changed_gltf_refs, save_scene_system,
+        update_window_title,
+    },
+    thumbnails::ThumbnailPlugin,
+    transform_gizmo::TransformGizmoPlugin,
     ui::ui_plugin::EditorUiPlugin,
 };
 fn main() {
     let mut app = App::new();
-    app.insert_resource(DefaultOpaqueRendererMethod::deferred())
+    // Extra asset roots have to be registered as asset sources before the AssetPlugin
+    let asset_roots = AssetRoots::load(&FileAssetReader::get_base_path());
+    for root in &asset_roots.extra_roots {
+        let root_path = asset_roots.root_path(root);
+        app.register_asset_source(
+            root.name.clone(),
+            AssetSourceBuilder::platform_default(&root_path.to_string_lossy(), None),
+        );
+    }
+    app.insert_resource(EditorAssetRoots(asset_roots))
+        .insert_resource(DefaultOpaqueRendererMethod::deferred())
         .insert_resource(DirectionalLightShadowMap { size: 4096 })
         .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_cursor_options: Some(CursorOptions {
-                    grab_mode: CursorGrabMode::Confined,
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_cursor_options: Some(CursorOptions {
+                        grab_mode: CursorGrabMode::Confined,
+                        ..default()
+                    }),
+                    ..default()
+                })
+                // Re-exported glTFs update every placed instance without restarting
+                .set(AssetPlugin {
+                    watch_for_changes_override: Some(true),
                     ..default()
                 }),
-                ..default()
-            }),
             WireframePlugin {
                 debug_flags: RenderDebugFlags::empty(),
             },
@@ -53,20 +92,41 @@ fn main() {
             CameraPlugin,
             EditorUiPlugin,
             EditorInputPlugin,
+            TransformGizmoPlugin,
+            AutosavePlugin,
+            ThumbnailPlugin,
+            MissingAssetsPlugin,
         ))
         .add_plugins(MaterialPlugin::<OutlineMaterial>::default())
         .insert_resource(Pause(true))
         .insert_resource(CurrentObjectManipulationMode::default())
-        .insert_resource(EditorGltfInstances::default())
         .register_type::<Transform>()
+        // Gameplay components designers attach in the component inspector
+        .register_type::<Interactable>()
+        .register_type::<Player>()
         .insert_resource(MoveState::default())
         .insert_resource(RotationEditState::default())
         .insert_resource(EditorSelected::default())
+        .insert_resource(EditorHistory::default())
+        .insert_resource(SnapSettings::default())
+        .insert_resource(CurrentLevel::default())
+        .insert_resource(EditorStatus::default())
         .add_observer(detect_gltf_children)
         .add_systems(Startup, setup_scene)
         .add_systems(Update, (animate_light_direction, switch_mode, spin))
-        .add_systems(Startup, load_scene_system)
-        .add_systems(Update, finish_loading_scene)
+        .add_systems(Startup, open_level_from_args)
+        .add_systems(
+            Update,
+            (
+                (
+                    reload_changed_gltf_refs,
+                    finish_loading_scene,
+                    build_gltf_part_scenes,
+                )
+                    .chain(),
+                update_window_title,
+            ),
+        )
         .run();
     let save_system_id = app.register_system(save_scene_system);
     app.register_type::<Transform>();
@@ -121,14 +181,17 @@ fn switch_mode(
     mut pause: ResMut<Pause>,
     mut hide_ui: Local<bool>,
     mut mode: Local<DefaultRenderMode>,
+    manip_mode: Res<CurrentObjectManipulationMode>,
 ) {
     text.clear();
+    // Digits type exact values while moving/rotating/scaling
+    let typing_value = manip_mode.mode != ManipulationMode::None;
 
     if keys.just_pressed(KeyCode::Space) {
         pause.0 = !pause.0;
     }
 
-    if keys.just_pressed(KeyCode::Digit1) {
+    if !typing_value && keys.just_pressed(KeyCode::Digit1) {
         *mode = DefaultRenderMode::Deferred;
         default_opaque_renderer_method.set_to_deferred();
         println!("DefaultOpaqueRendererMethod: Deferred");
@@ -140,7 +203,7 @@ fn switch_mode(
             commands.entity(camera).insert(DeferredPrepass);
         }
     }
-    if keys.just_pressed(KeyCode::Digit2) {
+    if !typing_value && keys.just_pressed(KeyCode::Digit2) {
         *mode = DefaultRenderMode::Forward;
         default_opaque_renderer_method.set_to_forward();
         println!("DefaultOpaqueRendererMethod: Forward");
@@ -152,7 +215,7 @@ fn switch_mode(
             commands.entity(camera).remove::<DeferredPrepass>();
         }
     }
-    if keys.just_pressed(KeyCode::Digit3) {
+    if !typing_value && keys.just_pressed(KeyCode::Digit3) {
         *mode = DefaultRenderMode::ForwardPrepass;
         default_opaque_renderer_method.set_to_forward();
         println!("DefaultOpaqueRendererMethod: Forward + Prepass");
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use bevy::{camera::primitives::Aabb, prelude::*, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+use egui::{Color32, Id, LayerId, Order, Pos2, Stroke, StrokeKind};
+
+use crate::{
+    camera::EditorCamera,
+    defs::{
+        CurrentObjectManipulationMode, EditorSelected, GltfEntityRoot, ManipulationMode,
+        TransformGizmoState,
+    },
+    helper_funcs::aabb_world_corners,
+    input::HighlightedMesh,
+};
+// How far the cursor has to travel before a press turns into a marquee drag
+const MARQUEE_DRAG_THRESHOLD: f32 = 4.0;
+
+/// Drag a rectangle in the viewport with the left mouse button to select every editor object
+/// whose meshes project fully inside it.
+/// Shift adds to the selection, Ctrl removes from it, otherwise the selection is replaced.
+pub fn marquee_selection(
+    mut egui_ctx: EguiContexts,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    cam_q: Query<(&GlobalTransform, &Camera), With<EditorCamera>>,
+    mesh_q: Query<(&GltfEntityRoot, &Aabb, &GlobalTransform), Without<HighlightedMesh>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    manip_mode: Res<CurrentObjectManipulationMode>,
+    gizmo_state: Res<TransformGizmoState>,
+    mut selected_entity: ResMut<EditorSelected>,
+    mut drag_start: Local<Option<Vec2>>,
+) {
+    let Ok(ctx) = egui_ctx.ctx_mut() else {
+        return;
+    };
+    let Ok(window) = window_q.single() else {
+        return;
+    };
+    let Some(cursor_position) = window.cursor_position() else {
+        return;
+    };
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        // Don't start a marquee over the ui, a gizmo handle or while moving/rotating/scaling
+        *drag_start = (!ctx.wants_pointer_input()
+            && manip_mode.mode == ManipulationMode::None
+            && gizmo_state.hovered.is_none())
+        .then_some(cursor_position);
+    }
+    let Some(start) = *drag_start else {
+        return;
+    };
+    let marquee = Rect::from_corners(start, cursor_position);
+    let is_drag = marquee.size().max_element() > MARQUEE_DRAG_THRESHOLD;
+
+    if mouse_buttons.pressed(MouseButton::Left) {
+        if is_drag {
+            let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("marquee")));
+            let rect = egui::Rect::from_min_max(
+                Pos2::new(marquee.min.x, marquee.min.y),
+                Pos2::new(marquee.max.x, marquee.max.y),
+            );
+            painter.rect(
+                rect,
+                0.0,
+                Color32::from_rgba_unmultiplied(255, 156, 0, 20),
+                Stroke::new(1.0, Color32::from_rgb(255, 156, 0)),
+                StrokeKind::Inside,
+            );
+        }
+        return;
+    }
+    // The button was released
+    *drag_start = None;
+    if !is_drag {
+        return;
+    }
+    let Ok((cam_transform, camera)) = cam_q.single() else {
+        return;
+    };
+
+    // Screen space bounds of every editor object, built from the bounds of its meshes.
+    // None means part of it is behind the camera, so it can't be fully inside the marquee
+    let mut screen_bounds: HashMap<Entity, Option<Rect>> = HashMap::new();
+    for (root, aabb, mesh_transform) in mesh_q {
+        let mesh_screen_rect = project_aabb(aabb, mesh_transform, cam_transform, camera);
+        let bounds = screen_bounds.entry(root.0).or_insert(mesh_screen_rect);
+        *bounds = match (*bounds, mesh_screen_rect) {
+            (Some(bounds), Some(mesh_rect)) => Some(bounds.union(mesh_rect)),
+            _ => None,
+        };
+    }
+    let inside: Vec<Entity> = screen_bounds
+        .into_iter()
+        .filter_map(|(entity, bounds)| {
+            let bounds = bounds?;
+            (marquee.contains(bounds.min) && marquee.contains(bounds.max)).then_some(entity)
+        })
+        .collect();
+
+    if keyboard_input.pressed(KeyCode::ControlLeft) {
+        selected_entity.0.retain(|entity| !inside.contains(entity));
+    } else {
+        if !keyboard_input.pressed(KeyCode::ShiftLeft) {
+            selected_entity.clear();
+        }
+        for entity in inside {
+            if !selected_entity.contains(entity) {
+                selected_entity.0.push(entity);
+            }
+        }
+    }
+}
+// Project the 8 corners of a mesh's bounding box into the viewport
+fn project_aabb(
+    aabb: &Aabb,
+    mesh_transform: &GlobalTransform,
+    cam_transform: &GlobalTransform,
+    camera: &Camera,
+) -> Option<Rect> {
+    let mut screen_rect: Option<Rect> = None;
+    for world_corner in aabb_world_corners(aabb, mesh_transform.affine()) {
+        let screen_corner = camera.world_to_viewport(cam_transform, world_corner).ok()?;
+        screen_rect = Some(match screen_rect {
+            Some(rect) => rect.union_point(screen_corner),
+            None => Rect::from_center_size(screen_corner, Vec2::ZERO),
+        });
+    }
+    screen_rect
+}
@@ -0,0 +1,271 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+use rfd::FileDialog;
+
+use crate::{
+    defs::{EditorAssetRoots, EditorEntityLink, EditorStatus, GltfRef},
+    editor_history::{EditorEdit, EditorHistory},
+    gltf_parts::{GltfFile, GltfPart},
+    helper_funcs::asset_path_or_import,
+};
+// Edge length of the cube shown in place of a glTF that couldn't be loaded
+const PLACEHOLDER_SIZE: f32 = 0.5;
+
+pub struct MissingAssetsPlugin;
+impl Plugin for MissingAssetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MissingAssetsPanel::default())
+            .add_systems(Startup, setup_placeholder_scene)
+            .add_systems(Update, show_missing_asset_placeholders);
+    }
+}
+
+/// On an editor object whose glTF file or label couldn't be loaded, until its GltfRef changes
+#[derive(Component)]
+pub struct MissingAsset;
+// The glTF scene child showing the placeholder instead of the missing glTF
+#[derive(Component)]
+struct MissingAssetPlaceholder;
+// Scene spawned for every missing glTF, so it's picked and highlighted like a loaded one
+#[derive(Resource)]
+struct PlaceholderScene(Handle<Scene>);
+#[derive(Resource, Default)]
+pub struct MissingAssetsPanel {
+    pub open: bool,
+    // Folder substitution, asset paths starting with `from_folder` get `to_folder` instead
+    pub from_folder: String,
+    pub to_folder: String,
+}
+
+fn setup_placeholder_scene(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut scenes: ResMut<Assets<Scene>>,
+) {
+    let mut world = World::new();
+    world.spawn((
+        Name::new("Missing Asset"),
+        Mesh3d(meshes.add(Cuboid::from_length(PLACEHOLDER_SIZE))),
+        // Unlit magenta stands out in any lighting
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgb(1.0, 0.0, 1.0),
+            unlit: true,
+            ..default()
+        })),
+        // Resting on the object's origin, like most props do
+        Transform::from_xyz(0.0, PLACEHOLDER_SIZE / 2.0, 0.0),
+        Visibility::default(),
+    ));
+    commands.insert_resource(PlaceholderScene(scenes.add(Scene::new(world))));
+}
+
+// Swap the scene of every glTF that failed to load for the placeholder
+fn show_missing_asset_placeholders(
+    links_q: Query<
+        (
+            Entity,
+            &EditorEntityLink,
+            &GltfFile,
+            Option<&SceneRoot>,
+            Option<&GltfPart>,
+        ),
+        Without<MissingAssetPlaceholder>,
+    >,
+    asset_server: Res<AssetServer>,
+    placeholder: Option<Res<PlaceholderScene>>,
+    mut panel: ResMut<MissingAssetsPanel>,
+    mut commands: Commands,
+) {
+    let Some(placeholder) = placeholder else {
+        return;
+    };
+    for (entity, editor_entity_link, file, scene_root, part) in &links_q {
+        let id = match (part, scene_root) {
+            // The scenes built for nodes and meshes aren't loaded by the asset server, the
+            // parts they're built from are
+            (Some(GltfPart::Node(node)), _) => node.id().untyped(),
+            (Some(GltfPart::Mesh(mesh)), _) => mesh.id().untyped(),
+            (None, Some(scene_root)) => scene_root.0.id().untyped(),
+            (None, None) => continue,
+        };
+        // A label the glTF doesn't have doesn't fail, the glTF just loads without it
+        let file_state = asset_server.load_state(&file.0);
+        if !file_state.is_failed() && !(file_state.is_loaded() && !asset_server.is_loaded(id)) {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .remove::<GltfPart>()
+            .insert((SceneRoot(placeholder.0.clone()), MissingAssetPlaceholder));
+        commands.entity(editor_entity_link.0).insert(MissingAsset);
+        panel.open = true;
+    }
+}
+
+pub fn toggle_missing_assets_system(mut panel: ResMut<MissingAssetsPanel>) {
+    panel.open = !panel.open;
+}
+/// Point every object missing `asset_path` at a glTF picked on disk
+pub fn relink_asset_system(In(asset_path): In<String>, world: &mut World) {
+    let asset_roots = world.resource::<EditorAssetRoots>();
+    let Some(path) = FileDialog::new()
+        .set_title(format!("Relink {}", asset_path))
+        .add_filter("glTF", &["glb", "gltf"])
+        .set_directory(asset_roots.default_root_path())
+        .pick_file()
+    else {
+        return;
+    };
+    let Some(new_path) = asset_path_or_import(&path, asset_roots) else {
+        return;
+    };
+    let relinked = relink_missing(world, |missing_path| {
+        (missing_path == asset_path).then(|| new_path.clone())
+    });
+    world.resource_mut::<EditorStatus>().info(format!(
+        "Relinked {} object(s) from {} to {}",
+        relinked, asset_path, new_path
+    ));
+}
+/// Replace the start of missing asset paths, for glTFs that moved to another folder.
+/// Only paths that exist after the replacement are relinked
+pub fn substitute_asset_folder_system(In((from, to)): In<(String, String)>, world: &mut World) {
+    let asset_roots = world.resource::<EditorAssetRoots>().0.clone();
+    let mut not_found = 0;
+    let relinked = relink_missing(world, |missing_path| {
+        let new_path = format!("{}{}", to, missing_path.strip_prefix(from.as_str())?);
+        if asset_roots
+            .file_path(&new_path)
+            .is_some_and(|file| file.is_file())
+        {
+            Some(new_path)
+        } else {
+            not_found += 1;
+            None
+        }
+    });
+    let message = format!("Relinked {} object(s) from {} to {}", relinked, from, to);
+    let mut status = world.resource_mut::<EditorStatus>();
+    if not_found > 0 {
+        status.error(format!(
+            "{}, {} object(s) aren't in {} either",
+            message, not_found, to
+        ));
+    } else {
+        status.info(message);
+    }
+}
+// Give the GltfRefs of missing objects new asset paths as one undoable edit, the new glTFs
+// load as soon as the GltfRefs change. Returns how many were relinked
+fn relink_missing(world: &mut World, mut new_path: impl FnMut(&str) -> Option<String>) -> usize {
+    let relinks: Vec<(Entity, GltfRef, GltfRef)> = world
+        .query_filtered::<(Entity, &GltfRef), With<MissingAsset>>()
+        .iter(world)
+        .filter_map(|(entity, gltf_ref)| {
+            let after = GltfRef {
+                asset_path: new_path(&gltf_ref.asset_path)?,
+                label: gltf_ref.label.clone(),
+            };
+            Some((entity, gltf_ref.clone(), after))
+        })
+        .collect();
+    let edits: Vec<EditorEdit> = relinks
+        .into_iter()
+        .map(|(entity, before, after)| {
+            world.entity_mut(entity).insert(after.clone());
+            EditorEdit::Component {
+                entity,
+                type_id: TypeId::of::<GltfRef>(),
+                before: Some(Box::new(before)),
+                after: Some(Box::new(after)),
+            }
+        })
+        .collect();
+    let relinked = edits.len();
+    if relinked > 0 {
+        world
+            .resource_mut::<EditorHistory>()
+            .push(EditorEdit::Group(edits));
+    }
+    relinked
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        editor_history::{redo_last_edit, undo_last_edit},
+        test_helpers::{editor_objects, spawn_object, test_app, use_temp_asset_roots},
+    };
+
+    fn gltf_path(world: &mut World, name: &str) -> String {
+        editor_objects(world)[name].gltf_ref.clone().unwrap().0
+    }
+
+    #[test]
+    fn substituted_folders_relink_as_one_undoable_edit() {
+        let mut app = test_app();
+        app.register_type::<GltfRef>();
+        let base_path = use_temp_asset_roots(&mut app, "substitute_asset_folder");
+        // Only the crate made it to the new folder
+        fs::create_dir_all(base_path.join("assets/models/new")).unwrap();
+        fs::write(base_path.join("assets/models/new/crate.glb"), "").unwrap();
+        let world = app.world_mut();
+        for (name, asset_path, missing) in [
+            ("Crate", "models/old/crate.glb", true),
+            ("Barrel", "models/old/barrel.glb", true),
+            // Loaded fine, so it's left alone even though the new folder has it too
+            ("Other crate", "models/old/crate.glb", false),
+        ] {
+            let entity = spawn_object(world, name, Transform::default());
+            world.entity_mut(entity).insert(GltfRef {
+                asset_path: asset_path.to_owned(),
+                label: None,
+            });
+            if missing {
+                world.entity_mut(entity).insert(MissingAsset);
+            }
+        }
+
+        substitute_asset_folder_system(
+            In(("models/old/".to_owned(), "models/new/".to_owned())),
+            world,
+        );
+        assert_eq!(gltf_path(world, "Crate"), "models/new/crate.glb");
+        assert_eq!(gltf_path(world, "Barrel"), "models/old/barrel.glb");
+        assert_eq!(gltf_path(world, "Other crate"), "models/old/crate.glb");
+        let status = world.resource::<EditorStatus>().message.as_ref().unwrap();
+        assert!(status.is_error);
+        assert!(status.text.contains("Relinked 1 object(s)"));
+        assert!(status.text.contains("1 object(s) aren't in models/new/"));
+
+        undo_last_edit(world);
+        assert_eq!(gltf_path(world, "Crate"), "models/old/crate.glb");
+        assert!(world.resource::<EditorHistory>().undo_stack.is_empty());
+        redo_last_edit(world);
+        assert_eq!(gltf_path(world, "Crate"), "models/new/crate.glb");
+        fs::remove_dir_all(&base_path).ok();
+    }
+
+    #[test]
+    fn nothing_to_relink_adds_no_edit() {
+        let mut app = test_app();
+        let world = app.world_mut();
+        let entity = spawn_object(world, "Crate", Transform::default());
+        world.entity_mut(entity).insert((
+            GltfRef {
+                asset_path: "models/crate.glb".to_owned(),
+                label: None,
+            },
+            MissingAsset,
+        ));
+
+        let relinked = relink_missing(world, |_| None);
+        assert_eq!(relinked, 0);
+        assert!(world.resource::<EditorHistory>().undo_stack.is_empty());
+    }
+}
@@ -0,0 +1,635 @@
+use std::{collections::HashMap, fs};
+
+use bevy::prelude::*;
+use common::level::{
+    CURRENT_LEVEL_VERSION, LevelEntity, LevelFile, PrefabInstance, PrefabOverride,
+};
+use rfd::FileDialog;
+
+use crate::{
+    camera::EditorCamera,
+    const_defs::PREFABS_FOLDER,
+    defs::{EditorAssetRoots, EditorChildOf, EditorSelected, EditorStatus, PrefabLink, PrefabRoot},
+    editor_history::{EditorEdit, EditorHistory, despawn_subtree},
+    saving_loading::{
+        editor_subtrees, level_from_world, level_transform, object_to_level_entity, spawn_level,
+        spawn_level_entity, write_atomically,
+    },
+};
+const PREFAB_FILE_EXTENSION: &str = "ron";
+
+/// Read a prefab file, with any prefabs inside it instantiated
+pub fn load_prefab(world: &World, path: &str) -> Result<LevelFile, String> {
+    let prefab = read_prefab_file(world, path)?;
+    if prefab.prefab_root().is_none() {
+        return Err("the prefab has no root object".to_owned());
+    }
+    Ok(prefab.instantiate_prefabs(&mut |nested_path| read_prefab_file(world, nested_path).ok()))
+}
+fn read_prefab_file(world: &World, path: &str) -> Result<LevelFile, String> {
+    let asset_roots = world.resource::<EditorAssetRoots>();
+    let file_path = asset_roots
+        .file_path(path)
+        .ok_or_else(|| "unknown asset root".to_owned())?;
+    fs::read_to_string(&file_path)
+        .map_err(|e| e.to_string())
+        .and_then(|prefab_ron| {
+            LevelFile::from_ron(&prefab_ron, asset_roots).map_err(|e| e.to_string())
+        })
+}
+/// The root of the prefab instance an object was spawned as part of
+pub fn prefab_instance_of(world: &World, entity: Entity) -> Option<Entity> {
+    let mut current = entity;
+    loop {
+        let entity_ref = world.get_entity(current).ok()?;
+        if entity_ref.contains::<PrefabRoot>() {
+            return Some(current);
+        }
+        // Objects added below a prefab object aren't part of the instance
+        if !entity_ref.contains::<PrefabLink>() {
+            return None;
+        }
+        current = entity_ref.get::<EditorChildOf>()?.0;
+    }
+}
+// The instance root and every object below it that came from its prefab, parents first
+fn instance_objects(world: &World, root: Entity) -> Vec<Entity> {
+    editor_subtrees(world, [root])
+        .into_iter()
+        .filter(|entity| prefab_instance_of(world, *entity) == Some(root))
+        .collect()
+}
+/// What a level stores for a prefab instance root, None for other objects
+pub fn prefab_instance(world: &World, root: Entity) -> Option<PrefabInstance> {
+    let prefab_root = world.get::<PrefabRoot>(root)?;
+    if let Some(overrides) = &prefab_root.unresolved_overrides {
+        return Some(PrefabInstance {
+            path: prefab_root.path.clone(),
+            overrides: overrides.clone(),
+        });
+    }
+    let prefab = prefab_root.prefab.as_ref();
+    let overrides = instance_objects(world, root)
+        .into_iter()
+        .filter_map(|entity| {
+            let source_id = world.get::<PrefabLink>(entity)?.source_id;
+            let object = object_to_level_entity(world, entity)?;
+            let prefab_entity =
+                prefab.and_then(|prefab| prefab.entities.iter().find(|e| e.id == source_id));
+            let mut prefab_override = match prefab_entity {
+                Some(prefab_entity) => PrefabOverride::between(prefab_entity, &object),
+                // Not in the prefab, keep everything so the instance looks the same
+                None => PrefabOverride {
+                    target: source_id,
+                    name: object.name,
+                    transform: Some(object.transform),
+                    components: Some(object.components),
+                },
+            };
+            // The instance's own transform places the root
+            if entity == root {
+                prefab_override.transform = None;
+            }
+            (!prefab_override.is_empty()).then_some(prefab_override)
+        })
+        .collect();
+    Some(PrefabInstance {
+        path: prefab_root.path.clone(),
+        overrides,
+    })
+}
+/// Spawn the objects of a prefab instance with the overrides applied, returning them root
+/// first. When the prefab can't be loaded only the root is spawned, keeping the overrides
+pub fn spawn_prefab_instance(
+    world: &mut World,
+    instance_entity: &LevelEntity,
+    instance: &PrefabInstance,
+    parent: Option<Entity>,
+) -> Vec<Entity> {
+    let prefab = match load_prefab(world, &instance.path) {
+        Ok(prefab) => prefab,
+        Err(e) => {
+            world
+                .resource_mut::<EditorStatus>()
+                .error(format!("Error loading prefab {}: {}", instance.path, e));
+            let root = spawn_level_entity(world, instance_entity, parent);
+            world.entity_mut(root).insert(PrefabRoot {
+                path: instance.path.clone(),
+                prefab: None,
+                unresolved_overrides: Some(instance.overrides.clone()),
+            });
+            return vec![root];
+        }
+    };
+    let prefab_root_id = prefab.prefab_root().unwrap().id;
+    let prefab_entities = LevelFile {
+        version: prefab.version,
+        entities: instance.apply(&prefab),
+    };
+    let mut spawned_ids: HashMap<u64, Entity> = HashMap::new();
+    let mut spawned = Vec::new();
+    for prefab_entity in prefab_entities.sorted_by_hierarchy() {
+        let entity = if prefab_entity.id == prefab_root_id {
+            let root_entity = LevelEntity {
+                id: instance_entity.id,
+                transform: instance_entity.transform,
+                ..prefab_entity.clone()
+            };
+            let root = spawn_level_entity(world, &root_entity, parent);
+            world.entity_mut(root).insert(PrefabRoot {
+                path: instance.path.clone(),
+                prefab: Some(prefab.clone()),
+                unresolved_overrides: None,
+            });
+            root
+        } else {
+            let parent = prefab_entity
+                .parent
+                .and_then(|parent| spawned_ids.get(&parent).copied());
+            spawn_level_entity(world, prefab_entity, parent)
+        };
+        world.entity_mut(entity).insert(PrefabLink {
+            source_id: prefab_entity.id,
+        });
+        spawned_ids.insert(prefab_entity.id, entity);
+        spawned.push(entity);
+    }
+    spawned
+}
+
+/// Save the active object and its editor children as a prefab file and turn them into an
+/// instance of it
+pub fn create_prefab_system(world: &mut World) {
+    let Some(root) = world.resource::<EditorSelected>().active() else {
+        return;
+    };
+    if world.get::<PrefabLink>(root).is_some() && world.get::<PrefabRoot>(root).is_none() {
+        world.resource_mut::<EditorStatus>().error(
+            "Part of a prefab instance can't be made into a prefab, only the whole instance",
+        );
+        return;
+    }
+    let asset_roots = world.resource::<EditorAssetRoots>();
+    let prefabs_folder = asset_roots.default_root_path().join(PREFABS_FOLDER);
+    fs::create_dir_all(&prefabs_folder).ok();
+    let file_name = world
+        .get::<Name>(root)
+        .map(|name| format!("{}.{}", name, PREFAB_FILE_EXTENSION))
+        .unwrap_or_else(|| format!("new_prefab.{}", PREFAB_FILE_EXTENSION));
+    let Some(path) = FileDialog::new()
+        .add_filter("Prefab", &[PREFAB_FILE_EXTENSION])
+        .set_directory(prefabs_folder)
+        .set_file_name(file_name)
+        .save_file()
+    else {
+        return;
+    };
+    let Some(asset_path) = asset_roots.asset_path(&path) else {
+        world.resource_mut::<EditorStatus>().error(format!(
+            "Prefabs have to be saved inside an asset folder, not {}",
+            path.display()
+        ));
+        return;
+    };
+    let subtree = editor_subtrees(world, [root]);
+    // An instance links to a single prefab, so prefabs inside it are stored expanded
+    let mut prefab = level_from_world(world, &subtree)
+        .instantiate_prefabs(&mut |nested_path| read_prefab_file(world, nested_path).ok());
+    // Instances are placed by their own transform
+    if let Some(prefab_root) = prefab
+        .entities
+        .iter_mut()
+        .find(|entity| entity.parent.is_none())
+    {
+        prefab_root.transform = default();
+    }
+    let written = prefab
+        .to_ron()
+        .map_err(|e| e.to_string())
+        .and_then(|prefab_ron| write_atomically(&path, &prefab_ron).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        world.resource_mut::<EditorStatus>().error(format!(
+            "Error writing prefab to {}: {}",
+            path.display(),
+            e
+        ));
+        return;
+    }
+    let Some(transform) = world.get::<Transform>(root).copied() else {
+        return;
+    };
+    let instance = LevelFile {
+        version: CURRENT_LEVEL_VERSION,
+        entities: vec![LevelEntity {
+            id: 0,
+            name: None,
+            parent: None,
+            transform: level_transform(&transform),
+            asset: None,
+            components: Vec::new(),
+            prefab: Some(PrefabInstance {
+                path: asset_path.clone(),
+                overrides: Vec::new(),
+            }),
+            sub_level: None,
+        }],
+    };
+    if let Some((edit, new_root)) = replace_with_level(world, root, &instance) {
+        world.resource_mut::<EditorHistory>().push(edit);
+        world.resource_mut::<EditorSelected>().select_only(new_root);
+    }
+    world
+        .resource_mut::<EditorStatus>()
+        .info(format!("Saved prefab {}", asset_path));
+}
+/// Pick a prefab file and spawn an instance of it in front of the camera
+pub fn add_prefab_system(world: &mut World) {
+    let asset_roots = world.resource::<EditorAssetRoots>();
+    let Some(path) = FileDialog::new()
+        .add_filter("Prefab", &[PREFAB_FILE_EXTENSION])
+        .set_directory(asset_roots.default_root_path().join(PREFABS_FOLDER))
+        .pick_file()
+    else {
+        return;
+    };
+    let Some(asset_path) = asset_roots.asset_path(&path) else {
+        world.resource_mut::<EditorStatus>().error(format!(
+            "Prefabs have to be inside an asset folder, {} isn't",
+            path.display()
+        ));
+        return;
+    };
+    let transform = world
+        .query_filtered::<&GlobalTransform, With<EditorCamera>>()
+        .single(world)
+        .map(GlobalTransform::compute_transform)
+        .unwrap_or_default();
+    let instance_entity = LevelEntity {
+        id: 0,
+        name: None,
+        parent: None,
+        transform: level_transform(&transform),
+        asset: None,
+        components: Vec::new(),
+        prefab: None,
+        sub_level: None,
+    };
+    let instance = PrefabInstance {
+        path: asset_path,
+        overrides: Vec::new(),
+    };
+    let root = spawn_prefab_instance(world, &instance_entity, &instance, None)[0];
+    world
+        .resource_mut::<EditorHistory>()
+        .push(EditorEdit::Spawn {
+            root,
+            snapshot: Vec::new(),
+        });
+    world.resource_mut::<EditorSelected>().select_only(root);
+}
+/// Write the selected instance's overrides into its prefab file and update every instance
+/// of the prefab, objects with their own override of a property keep it
+pub fn apply_prefab_overrides_system(world: &mut World) {
+    let Some(root) = selected_prefab_instance(world) else {
+        return;
+    };
+    let prefab_root = world.get::<PrefabRoot>(root).unwrap();
+    let path = prefab_root.path.clone();
+    let Some(old_prefab) = prefab_root.prefab.clone() else {
+        world.resource_mut::<EditorStatus>().error(format!(
+            "Prefab {} couldn't be loaded, there's nothing to apply to",
+            path
+        ));
+        return;
+    };
+    // The prefab as the instance looks now, objects keep their ids from the prefab file
+    let source_ids: HashMap<Entity, u64> = instance_objects(world, root)
+        .into_iter()
+        .filter_map(|entity| Some((entity, world.get::<PrefabLink>(entity)?.source_id)))
+        .collect();
+    let root_source_id = source_ids.get(&root).copied();
+    let prefab_entities = instance_objects(world, root)
+        .into_iter()
+        .filter_map(|entity| {
+            let source_id = source_ids.get(&entity).copied()?;
+            let mut prefab_entity = object_to_level_entity(world, entity)?;
+            prefab_entity.id = source_id;
+            if entity == root {
+                prefab_entity.transform = old_prefab
+                    .entities
+                    .iter()
+                    .find(|old_entity| old_entity.id == source_id)
+                    .map(|old_entity| old_entity.transform)
+                    .unwrap_or_default();
+            } else {
+                prefab_entity.parent = world
+                    .get::<EditorChildOf>(entity)
+                    .and_then(|parent| source_ids.get(&parent.0).copied())
+                    .or(root_source_id);
+            }
+            Some(prefab_entity)
+        })
+        .collect();
+    let prefab = LevelFile {
+        version: CURRENT_LEVEL_VERSION,
+        entities: prefab_entities,
+    };
+
+    // Every instance as it's stored in a level, with its overrides against the old prefab
+    let instance_roots: Vec<Entity> = world
+        .query::<(Entity, &PrefabRoot)>()
+        .iter(world)
+        .filter(|(_, prefab_root)| prefab_root.path == path)
+        .map(|(entity, _)| entity)
+        .collect();
+    // Instances inside another instance are respawned with it
+    let instance_roots: Vec<Entity> = instance_roots
+        .iter()
+        .copied()
+        .filter(|entity| {
+            let mut current = *entity;
+            while let Some(parent) = world.get::<EditorChildOf>(current) {
+                if instance_roots.contains(&parent.0) {
+                    return false;
+                }
+                current = parent.0;
+            }
+            true
+        })
+        .collect();
+    let mut instance_levels: Vec<(Entity, LevelFile)> = instance_roots
+        .into_iter()
+        .map(|entity| {
+            let subtree = editor_subtrees(world, [entity]);
+            (entity, level_from_world(world, &subtree))
+        })
+        .collect();
+    // The applied instance matches the new prefab now
+    for (entity, level) in &mut instance_levels {
+        if *entity != root {
+            continue;
+        }
+        if let Some(instance) = &mut level.entities[0].prefab {
+            instance.overrides.clear();
+        }
+    }
+
+    let file_path = world.resource::<EditorAssetRoots>().file_path(&path);
+    let written = file_path
+        .ok_or_else(|| "unknown asset root".to_owned())
+        .and_then(|file_path| {
+            let prefab_ron = prefab.to_ron().map_err(|e| e.to_string())?;
+            write_atomically(&file_path, &prefab_ron).map_err(|e| e.to_string())
+        });
+    if let Err(e) = written {
+        world
+            .resource_mut::<EditorStatus>()
+            .error(format!("Error writing prefab {}: {}", path, e));
+        return;
+    }
+    // Undo brings the old objects back, they're saved as overrides of the new prefab then
+    let mut edits = Vec::new();
+    for (entity, level) in instance_levels {
+        if let Some((edit, new_root)) = replace_with_level(world, entity, &level) {
+            edits.push(edit);
+            if entity == root {
+                world.resource_mut::<EditorSelected>().select_only(new_root);
+            }
+        }
+    }
+    let instance_count = edits.len();
+    world
+        .resource_mut::<EditorHistory>()
+        .push(EditorEdit::Group(edits));
+    world.resource_mut::<EditorStatus>().info(format!(
+        "Applied overrides to prefab {}, updated {} instances",
+        path, instance_count
+    ));
+}
+/// Drop the selected instance's overrides, objects added below it are kept
+pub fn revert_to_prefab_system(world: &mut World) {
+    let Some(root) = selected_prefab_instance(world) else {
+        return;
+    };
+    let subtree = editor_subtrees(world, [root]);
+    let mut level = level_from_world(world, &subtree);
+    if let Some(instance) = &mut level.entities[0].prefab {
+        instance.overrides.clear();
+    }
+    if let Some((edit, new_root)) = replace_with_level(world, root, &level) {
+        world.resource_mut::<EditorHistory>().push(edit);
+        world.resource_mut::<EditorSelected>().select_only(new_root);
+    }
+}
+fn selected_prefab_instance(world: &mut World) -> Option<Entity> {
+    let root = world
+        .resource::<EditorSelected>()
+        .active()
+        .and_then(|entity| prefab_instance_of(world, entity));
+    if root.is_none() {
+        world
+            .resource_mut::<EditorStatus>()
+            .error("The selected object isn't part of a prefab instance");
+    }
+    root
+}
+// Despawn `old_root` and spawn `level` in its place, under the same parent with the same
+// local transform. Returns the undoable edit and the new root
+fn replace_with_level(
+    world: &mut World,
+    old_root: Entity,
+    level: &LevelFile,
+) -> Option<(EditorEdit, Entity)> {
+    let transform = world.get::<Transform>(old_root).copied()?;
+    let editor_parent = world.get::<EditorChildOf>(old_root).map(|parent| parent.0);
+    let snapshot = despawn_subtree(world, old_root);
+    let new_root = spawn_level(world, level).first().copied()?;
+    let mut root_mut = world.entity_mut(new_root);
+    root_mut.insert(transform);
+    if let Some(parent) = editor_parent {
+        root_mut.insert((EditorChildOf(parent), ChildOf(parent)));
+    }
+    let edit = EditorEdit::Group(vec![
+        EditorEdit::Delete {
+            root: old_root,
+            snapshot,
+        },
+        EditorEdit::Spawn {
+            root: new_root,
+            snapshot: Vec::new(),
+        },
+    ]);
+    Some((edit, new_root))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use common::asset_roots::AssetRoots;
+
+    use super::*;
+    use crate::{
+        editor_history::undo_last_edit,
+        saving_loading::editor_level,
+        test_helpers::{editor_objects, find_object, test_app, use_temp_asset_roots},
+    };
+
+    // A level with one instance of a "Table" > "Lamp", "Cup" prefab written into a temporary
+    // asset folder the app's asset roots point to
+    fn spawn_prefab_test_level(app: &mut App, test_name: &str) -> PathBuf {
+        let base_path = use_temp_asset_roots(app, test_name);
+        let prefab_path = base_path.join("assets/prefabs/table.ron");
+        fs::create_dir_all(prefab_path.parent().unwrap()).unwrap();
+        fs::write(
+            &prefab_path,
+            r#"(version: 3, entities: [
+                (id: 0, name: Some("Table"), asset: Some((path: "models/table.glb"))),
+                (id: 1, name: Some("Lamp"), parent: Some(0), transform: (translation: (0.0, 1.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0))),
+                (id: 2, name: Some("Cup"), parent: Some(0)),
+            ])"#,
+        )
+        .unwrap();
+        let level = LevelFile::from_ron(
+            r#"(version: 3, entities: [
+                (id: 0, transform: (translation: (5.0, 0.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)), prefab: Some((path: "prefabs/table.ron"))),
+            ])"#,
+            &AssetRoots::default(),
+        )
+        .unwrap();
+        spawn_level(app.world_mut(), &level);
+        app.update();
+        base_path
+    }
+
+    #[test]
+    fn prefab_changes_reach_instances_without_overrides() {
+        let mut app = test_app();
+        let base_path = spawn_prefab_test_level(&mut app, "prefab_overrides");
+        let world = app.world_mut();
+        assert_eq!(editor_objects(world).len(), 3);
+        let lamp = find_object(world, "Lamp");
+        world.entity_mut(lamp).insert(Name::new("Desk lamp"));
+
+        // Only the renamed lamp is stored, the rest comes from the prefab
+        let level = editor_level(world);
+        assert_eq!(level.entities.len(), 1);
+        let instance = level.entities[0].prefab.as_ref().unwrap();
+        assert_eq!(instance.overrides.len(), 1);
+        assert_eq!(instance.overrides[0].target, 1);
+        assert_eq!(instance.overrides[0].name.as_deref(), Some("Desk lamp"));
+
+        // Changes to the lamp's name are overridden, its transform and the cup aren't
+        fs::write(
+            base_path.join("assets/prefabs/table.ron"),
+            r#"(version: 3, entities: [
+                (id: 0, name: Some("Table"), asset: Some((path: "models/table.glb"))),
+                (id: 1, name: Some("Floor lamp"), parent: Some(0), transform: (translation: (0.0, 2.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0))),
+                (id: 2, name: Some("Mug"), parent: Some(0)),
+            ])"#,
+        )
+        .unwrap();
+        let mut reloaded_app = test_app();
+        reloaded_app.insert_resource(EditorAssetRoots(AssetRoots {
+            base_path: base_path.clone(),
+            extra_roots: Vec::new(),
+        }));
+        spawn_level(reloaded_app.world_mut(), &level);
+        reloaded_app.update();
+        let reloaded = editor_objects(reloaded_app.world_mut());
+        fs::remove_dir_all(&base_path).ok();
+        assert_eq!(
+            reloaded["Table"].transform,
+            Transform::from_xyz(5.0, 0.0, 0.0)
+        );
+        assert_eq!(reloaded["Desk lamp"].parent.as_deref(), Some("Table"));
+        assert_eq!(
+            reloaded["Desk lamp"].transform,
+            Transform::from_xyz(0.0, 2.0, 0.0)
+        );
+        assert!(reloaded.contains_key("Mug"));
+    }
+
+    #[test]
+    fn prefab_changes_after_spawning_are_not_saved_as_overrides() {
+        let mut app = test_app();
+        let base_path = spawn_prefab_test_level(&mut app, "prefab_changed_on_disk");
+        let world = app.world_mut();
+        let cup = find_object(world, "Cup");
+        world.entity_mut(cup).insert(Name::new("Vase"));
+        // Someone else updates the prefab while the level is open
+        fs::write(
+            base_path.join("assets/prefabs/table.ron"),
+            r#"(version: 3, entities: [
+                (id: 0, name: Some("Desk"), asset: Some((path: "models/desk.glb"))),
+                (id: 1, name: Some("Floor lamp"), parent: Some(0), transform: (translation: (0.0, 2.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0))),
+                (id: 2, name: Some("Mug"), parent: Some(0)),
+            ])"#,
+        )
+        .unwrap();
+
+        // Only what was changed in the editor is an override, the rest follows the new prefab
+        let level = editor_level(world);
+        fs::remove_dir_all(&base_path).ok();
+        let instance = level.entities[0].prefab.as_ref().unwrap();
+        assert_eq!(instance.overrides.len(), 1);
+        assert_eq!(instance.overrides[0].target, 2);
+        assert_eq!(instance.overrides[0].name.as_deref(), Some("Vase"));
+        assert_eq!(instance.overrides[0].transform, None);
+        assert_eq!(instance.overrides[0].components, None);
+    }
+
+    #[test]
+    fn apply_and_revert_prefab_overrides() {
+        let mut app = test_app();
+        let base_path = spawn_prefab_test_level(&mut app, "prefab_apply");
+        let world = app.world_mut();
+        let cup = find_object(world, "Cup");
+        world.entity_mut(cup).insert(Name::new("Vase"));
+        world.resource_mut::<EditorSelected>().select_only(cup);
+
+        apply_prefab_overrides_system(world);
+        let prefab = fs::read_to_string(base_path.join("assets/prefabs/table.ron")).unwrap();
+        let prefab = LevelFile::from_ron(&prefab, &AssetRoots::default()).unwrap();
+        assert_eq!(prefab.entities[2].name.as_deref(), Some("Vase"));
+        // The instance matches the prefab now
+        let level = editor_level(world);
+        assert!(
+            level.entities[0]
+                .prefab
+                .as_ref()
+                .unwrap()
+                .overrides
+                .is_empty()
+        );
+
+        let lamp = find_object(world, "Lamp");
+        world
+            .entity_mut(lamp)
+            .insert(Transform::from_xyz(3.0, 3.0, 3.0));
+        world.resource_mut::<EditorSelected>().select_only(lamp);
+        revert_to_prefab_system(world);
+        app.update();
+        let objects = editor_objects(app.world_mut());
+        fs::remove_dir_all(&base_path).ok();
+        assert_eq!(objects.len(), 3);
+        assert_eq!(
+            objects["Lamp"].transform,
+            Transform::from_xyz(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            objects["Table"].transform,
+            Transform::from_xyz(5.0, 0.0, 0.0)
+        );
+        assert!(objects.contains_key("Vase"));
+
+        // Undoing the revert brings the moved lamp back
+        undo_last_edit(app.world_mut());
+        app.update();
+        let objects = editor_objects(app.world_mut());
+        assert_eq!(
+            objects["Lamp"].transform,
+            Transform::from_xyz(3.0, 3.0, 3.0)
+        );
+    }
+}
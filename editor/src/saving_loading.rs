@@ -1,57 +1,580 @@
 use std::{
+    any::TypeId,
+    collections::HashMap,
     fs::{self, File},
-    io::Write,
-    path::Path,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use bevy::{asset::ron, prelude::*, tasks::IoTaskPool};
+use bevy::{prelude::*, window::PrimaryWindow};
+use common::{
+    Interactable, Player,
+    level::{
+        AssetRef, CURRENT_LEVEL_VERSION, LevelComponent, LevelEntity, LevelFile, LevelTransform,
+        SubLevelRef,
+    },
+};
+use rfd::{FileDialog, MessageButtons, MessageDialog, MessageDialogResult};
 
 use crate::{
+    autosave::{remove_recovery_file, restore_recovery},
     defs::{
-        EditorChildOf, EditorEntityLink, EditorGltfInstance, EditorGltfInstances,
-        FinishedGltfRefLoading, GltfRef, IncludeInSave, InstantiatedGltfInstance,
+        CurrentLevel, CurrentObjectManipulationMode, EditorAssetRoots, EditorChildOf,
+        EditorEntityLink, EditorSelected, EditorStatus, FinishedGltfRefLoading, GltfRef,
+        IncludeInSave, ManipulationMode, PrefabRoot, SubLevelRoot,
     },
-    helper_funcs::strip_assets_prefix,
+    editor_history::EditorHistory,
+    gltf_parts::{GltfFile, load_gltf_ref},
+    missing_assets::MissingAsset,
+    prefabs::{prefab_instance, prefab_instance_of, spawn_prefab_instance},
+    sub_levels::{save_sub_levels, spawn_sub_level, sub_level_of},
 };
+const LEVEL_FILE_EXTENSION: &str = "ron";
+// How many "<level file>.<timestamp>.bak" copies of older saves are kept next to a level
+const MAX_LEVEL_BACKUPS: usize = 5;
+
+/// The given editor objects followed by their editor children, parents always before children.
+/// Objects already collected as part of another subtree are skipped
+pub fn editor_subtrees(world: &World, roots: impl IntoIterator<Item = Entity>) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    for root in roots {
+        collect_editor_subtree(world, root, &mut entities);
+    }
+    entities
+}
+fn collect_editor_subtree(world: &World, entity: Entity, entities: &mut Vec<Entity>) {
+    let Ok(entity_ref) = world.get_entity(entity) else {
+        return;
+    };
+    if !entity_ref.contains::<IncludeInSave>() || entities.contains(&entity) {
+        return;
+    }
+    entities.push(entity);
+    if let Some(children) = entity_ref.get::<Children>() {
+        for child in children.iter() {
+            collect_editor_subtree(world, child, entities);
+        }
+    }
+}
+/// Build the level that gets written to level files and the clipboard.
+/// Objects whose editor parent isn't part of `entities` become top level objects, keeping
+/// where they are in the world. Prefab instances are written as a reference to the prefab
+/// and their overrides, sub-levels as a reference to their file
+pub fn level_from_world(world: &World, entities: &[Entity]) -> LevelFile {
+    level_from_world_relative_to(world, entities, None)
+}
+/// Like [`level_from_world`], but children of `relative_to` keep their local transform as
+/// top level objects, for writing the contents of a sub-level
+pub fn level_from_world_relative_to(
+    world: &World,
+    entities: &[Entity],
+    relative_to: Option<Entity>,
+) -> LevelFile {
+    // Objects of a prefab instance that's saved too come from the prefab file
+    let in_saved_instance = |entity: Entity| {
+        !world.entity(entity).contains::<PrefabRoot>()
+            && prefab_instance_of(world, entity).is_some_and(|root| entities.contains(&root))
+    };
+    // and the contents of a sub-level are saved to its own file
+    let in_saved_sub_level =
+        |entity: Entity| sub_level_of(world, entity).is_some_and(|root| entities.contains(&root));
+    let saved: Vec<Entity> = entities
+        .iter()
+        .copied()
+        .filter(|entity| {
+            world.get_entity(*entity).is_ok()
+                && !in_saved_instance(*entity)
+                && !in_saved_sub_level(*entity)
+        })
+        .collect();
+    let ids: HashMap<Entity, u64> = saved
+        .iter()
+        .enumerate()
+        .map(|(index, entity)| (*entity, index as u64))
+        .collect();
+    let level_entities = saved
+        .iter()
+        .filter_map(|entity| {
+            let mut level_entity = object_to_level_entity(world, *entity)?;
+            let editor_parent = world.get::<EditorChildOf>(*entity).map(|parent| parent.0);
+            // Objects added below a part of a prefab instance are stored below the instance
+            let saved_parent = editor_parent.map(|parent| {
+                if in_saved_instance(parent) {
+                    prefab_instance_of(world, parent).unwrap()
+                } else {
+                    parent
+                }
+            });
+            level_entity.id = ids[entity];
+            level_entity.parent = saved_parent.and_then(|parent| ids.get(&parent).copied());
+            let global_transform = world.get::<GlobalTransform>(*entity);
+            // Children of `relative_to` keep their local transform
+            if editor_parent.is_some()
+                && editor_parent != relative_to
+                && level_entity.parent.is_none()
+            {
+                let transform = global_transform
+                    .map(GlobalTransform::compute_transform)
+                    .unwrap_or_default();
+                level_entity.transform = level_transform(&transform);
+            } else if saved_parent != editor_parent {
+                let transform = global_transform
+                    .zip(saved_parent.and_then(|parent| world.get::<GlobalTransform>(parent)))
+                    .map(|(global_transform, parent_global_transform)| {
+                        global_transform.reparented_to(parent_global_transform)
+                    })
+                    .unwrap_or_default();
+                level_entity.transform = level_transform(&transform);
+            }
+            if let Some(instance) = prefab_instance(world, *entity) {
+                level_entity = LevelEntity {
+                    name: None,
+                    asset: None,
+                    components: Vec::new(),
+                    prefab: Some(instance),
+                    ..level_entity
+                };
+            }
+            Some(level_entity)
+        })
+        .collect();
+    LevelFile {
+        version: CURRENT_LEVEL_VERSION,
+        entities: level_entities,
+    }
+}
+/// An editor object's name, local transform, asset and gameplay components as a level
+/// entity without an id or parent
+pub fn object_to_level_entity(world: &World, entity: Entity) -> Option<LevelEntity> {
+    let entity_ref = world.get_entity(entity).ok()?;
+    Some(LevelEntity {
+        id: 0,
+        name: entity_ref.get::<Name>().map(|name| name.to_string()),
+        parent: None,
+        transform: level_transform(&entity_ref.get::<Transform>().copied().unwrap_or_default()),
+        asset: entity_ref.get::<GltfRef>().map(|gltf_ref| AssetRef {
+            path: gltf_ref.asset_path.clone(),
+            label: gltf_ref.label.clone(),
+        }),
+        components: gameplay_components(entity_ref),
+        prefab: None,
+        sub_level: entity_ref
+            .get::<SubLevelRoot>()
+            .map(|sub_level| SubLevelRef {
+                path: sub_level.path.clone(),
+                load_distance: sub_level.load_distance,
+            }),
+    })
+}
+/// Spawn the objects of a level as editor objects, returning them parents first
+pub fn spawn_level(world: &mut World, level: &LevelFile) -> Vec<Entity> {
+    spawn_level_below(world, level, None)
+}
+/// Like [`spawn_level`], with the top level objects as editor children of `parent`
+pub fn spawn_level_below(
+    world: &mut World,
+    level: &LevelFile,
+    parent: Option<Entity>,
+) -> Vec<Entity> {
+    let top_level_parent = parent;
+    let mut spawned_ids: HashMap<u64, Entity> = HashMap::new();
+    let mut spawned = Vec::new();
+    for level_entity in level.sorted_by_hierarchy() {
+        let parent = level_entity
+            .parent
+            .and_then(|parent| spawned_ids.get(&parent).copied())
+            .or(top_level_parent);
+        let entities = match (&level_entity.prefab, &level_entity.sub_level) {
+            (Some(instance), _) => spawn_prefab_instance(world, level_entity, instance, parent),
+            (None, Some(sub_level)) => spawn_sub_level(world, level_entity, sub_level, parent),
+            (None, None) => vec![spawn_level_entity(world, level_entity, parent)],
+        };
+        spawned_ids.insert(level_entity.id, entities[0]);
+        spawned.extend(entities);
+    }
+    spawned
+}
+/// Spawn a single editor object, ignoring its `prefab` and `sub_level`
+pub fn spawn_level_entity(
+    world: &mut World,
+    level_entity: &LevelEntity,
+    parent: Option<Entity>,
+) -> Entity {
+    let name = level_entity
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("Object {}", level_entity.id));
+    let mut entity_mut = world.spawn((
+        Name::new(name),
+        transform_from_level(&level_entity.transform),
+        Visibility::Visible,
+        IncludeInSave,
+    ));
+    // finish_loading_scene spawns the glTF scene under it
+    if let Some(asset) = &level_entity.asset {
+        entity_mut.insert(GltfRef {
+            asset_path: asset.path.clone(),
+            label: asset.label.clone(),
+        });
+    }
+    insert_gameplay_components(&mut entity_mut, &level_entity.components);
+    if let Some(parent) = parent {
+        entity_mut.insert((EditorChildOf(parent), ChildOf(parent)));
+    }
+    entity_mut.id()
+}
+/// The gameplay components of an editor object as the level file lists them
+pub fn gameplay_components(entity_ref: EntityRef) -> Vec<LevelComponent> {
+    let mut components = Vec::new();
+    if let Some(interactable) = entity_ref.get::<Interactable>() {
+        components.push(LevelComponent::Interactable(interactable.clone()));
+    }
+    if let Some(player) = entity_ref.get::<Player>() {
+        components.push(LevelComponent::Player(player.clone()));
+    }
+    components
+}
+/// The types [`gameplay_components`] reads, the only components that are saved, copied and
+/// restored by undo besides the ones the editor manages itself
+pub fn gameplay_component_types() -> [TypeId; 2] {
+    [TypeId::of::<Interactable>(), TypeId::of::<Player>()]
+}
+pub fn insert_gameplay_components(entity_mut: &mut EntityWorldMut, components: &[LevelComponent]) {
+    for component in components {
+        match component {
+            LevelComponent::Interactable(interactable) => {
+                entity_mut.insert(interactable.clone());
+            }
+            LevelComponent::Player(player) => {
+                entity_mut.insert(player.clone());
+            }
+        }
+    }
+}
+pub fn level_transform(transform: &Transform) -> LevelTransform {
+    LevelTransform {
+        translation: transform.translation.to_array(),
+        rotation: transform.rotation.to_array(),
+        scale: transform.scale.to_array(),
+    }
+}
+pub fn transform_from_level(transform: &LevelTransform) -> Transform {
+    Transform {
+        translation: Vec3::from_array(transform.translation),
+        rotation: Quat::from_array(transform.rotation),
+        scale: Vec3::from_array(transform.scale),
+    }
+}
+
+/// Save to the current level file, asking for a path if the level was never saved
 pub fn save_scene_system(world: &mut World) {
-    let type_registry = world.resource::<AppTypeRegistry>().clone();
-    let type_registry = type_registry.read();
-    let entities_to_save: Vec<Entity> = world
-        .query::<(Entity, &IncludeInSave)>()
+    let path = match world.resource::<CurrentLevel>().path.clone() {
+        Some(path) => Some(path),
+        None => pick_save_path(world),
+    };
+    if let Some(path) = path {
+        save_level(world, path);
+    }
+}
+pub fn save_scene_as_system(world: &mut World) {
+    if let Some(path) = pick_save_path(world) {
+        save_level(world, path);
+    }
+}
+/// Every editor object in the world as a level
+pub fn editor_level(world: &mut World) -> LevelFile {
+    let roots: Vec<Entity> = world
+        .query_filtered::<Entity, (With<IncludeInSave>, Without<EditorChildOf>)>()
         .iter(world)
-        .map(|(entity, _)| entity)
         .collect();
-    // 2. Create the DynamicSceneBuilder
-    let scene_builder =
-        DynamicSceneBuilder::from_world(world).extract_entities(entities_to_save.into_iter());
-
-    // 4. Optionally: Add a component filter (see C below)
-
-    let scene = scene_builder.build();
-
-    // 5. Serialize and save to a file
-    match scene.serialize(&type_registry) {
-        Ok(ron) => {
-            // In a real editor, you'd save this string to a file (e.g., using a non-blocking task)
-            println!("Serialized Scene:\n{}", ron);
-            IoTaskPool::get()
-                .spawn(async move {
-                    // Write the scene RON data to file
-                    File::create(format!("assets/{}", SCENE_FILE_PATH))
-                        .and_then(|mut file| file.write(ron.as_bytes()))
-                        .expect("Error while writing scene to file");
-                })
-                .detach();
-        }
+    let entities_to_save = editor_subtrees(world, roots);
+    level_from_world(world, &entities_to_save)
+}
+fn save_level(world: &mut World, path: PathBuf) {
+    let ron = match editor_level(world).to_ron() {
+        Ok(ron) => ron,
         Err(e) => {
-            eprintln!("Error serializing scene: {}", e);
+            world
+                .resource_mut::<EditorStatus>()
+                .error(format!("Error serializing level: {}", e));
+            return;
         }
+    };
+    // A failed backup shouldn't stop the save, the old file is still replaced atomically
+    let backup_error = backup_level(&path).err();
+    if let Err(e) = write_atomically(&path, &ron) {
+        world.resource_mut::<EditorStatus>().error(format!(
+            "Error writing level to {}: {}",
+            path.display(),
+            e
+        ));
+        return;
     }
+    let mut problems: Vec<String> = backup_error
+        .map(|e| format!("backing up the previous version failed: {}", e))
+        .into_iter()
+        .collect();
+    problems.extend(save_sub_levels(world));
+    let mut status = world.resource_mut::<EditorStatus>();
+    if problems.is_empty() {
+        status.info(format!("Saved level to {}", path.display()));
+    } else {
+        status.error(format!(
+            "Saved level to {}, but {}",
+            path.display(),
+            problems.join(", ")
+        ));
+    }
+    world.resource_mut::<CurrentLevel>().path = Some(path);
+    world.resource_mut::<EditorHistory>().mark_saved();
+    remove_recovery_file(world);
+}
+/// Write to a temporary file next to `path` and rename it over `path`, so a crash or a full
+/// disk leaves either the old or the new file, never a truncated one
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let written = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    if written.is_err() {
+        fs::remove_file(&temp_path).ok();
+    }
+    written
 }
-const SCENE_FILE_PATH: &str = "test_saving_dynamicscene.scn.ron";
+// Copy the level about to be overwritten to "<level file>.<timestamp>-<counter>.bak", keeping
+// the newest MAX_LEVEL_BACKUPS of them
+pub fn backup_level(path: &Path) -> io::Result<()> {
+    backup_level_at(path, SystemTime::now())
+}
+fn backup_level_at(path: &Path, time: SystemTime) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let (Some(folder), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Ok(());
+    };
+    let file_name = file_name.to_string_lossy();
+    let timestamp = utc_timestamp(time);
+    // The counter tells apart saves within the same millisecond, padded so every name has the
+    // same shape
+    let backup_path = (0..)
+        .map(|number| format!("{}.{}-{:03}.bak", file_name, timestamp, number))
+        .map(|backup_name| path.with_file_name(backup_name))
+        .find(|backup_path| !backup_path.exists())
+        .unwrap();
+    fs::copy(path, &backup_path)?;
 
-pub fn load_scene_system(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(DynamicSceneRoot(asset_server.load(SCENE_FILE_PATH)));
+    let backup_prefix = format!("{}.", file_name);
+    let mut backups: Vec<PathBuf> = fs::read_dir(if folder.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        folder
+    })?
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|entry_path| {
+        entry_path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .is_some_and(|name| name.starts_with(&backup_prefix) && name.ends_with(".bak"))
+    })
+    .collect();
+    // The timestamps and counters sort the same way as the names, oldest first
+    backups.sort();
+    let excess = backups.len().saturating_sub(MAX_LEVEL_BACKUPS);
+    for old_backup in &backups[..excess] {
+        fs::remove_file(old_backup)?;
+    }
+    Ok(())
+}
+// "20261017-142501-042", UTC so backups sort correctly across time zone changes
+fn utc_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+    // Civil date from days since 1970-01-01, Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+fn pick_save_path(world: &World) -> Option<PathBuf> {
+    let mut dialog = level_file_dialog(world);
+    if let Some(file_name) = world
+        .resource::<CurrentLevel>()
+        .path
+        .as_ref()
+        .and_then(|path| path.file_name())
+    {
+        dialog = dialog.set_file_name(file_name.to_string_lossy());
+    } else {
+        dialog = dialog.set_file_name("new_level.ron");
+    }
+    dialog.save_file()
+}
+fn level_file_dialog(world: &World) -> FileDialog {
+    // Start next to the current level, or in the assets folder
+    let directory = world
+        .resource::<CurrentLevel>()
+        .path
+        .as_ref()
+        .and_then(|path| path.parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| world.resource::<EditorAssetRoots>().default_root_path());
+    FileDialog::new()
+        .add_filter("Level", &[LEVEL_FILE_EXTENSION])
+        .set_directory(directory)
+}
+
+pub fn open_level_system(world: &mut World) {
+    if !confirm_discard_changes(world) {
+        return;
+    }
+    if let Some(path) = level_file_dialog(world).pick_file() {
+        open_level(world, path);
+    }
+}
+pub fn new_level_system(world: &mut World) {
+    if !confirm_discard_changes(world) {
+        return;
+    }
+    clear_level(world);
+    world.resource_mut::<CurrentLevel>().path = None;
+    remove_recovery_file(world);
+}
+/// Offer to restore unsaved changes from the last session, otherwise open the level passed
+/// on the command line, e.g. `editor assets/levels/first.ron`
+pub fn open_level_from_args(world: &mut World) {
+    if restore_recovery(world) {
+        return;
+    }
+    if let Some(path) = std::env::args().nth(1) {
+        open_level(world, PathBuf::from(path));
+    }
+}
+pub fn open_level(world: &mut World, path: PathBuf) {
+    let level = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|level_ron| {
+            LevelFile::from_ron(&level_ron, world.resource::<EditorAssetRoots>())
+                .map_err(|e| e.to_string())
+        });
+    let level = match level {
+        Ok(level) => level,
+        Err(e) => {
+            world.resource_mut::<EditorStatus>().error(format!(
+                "Error loading level {}: {}",
+                path.display(),
+                e
+            ));
+            return;
+        }
+    };
+    clear_level(world);
+    spawn_level(world, &level);
+    world
+        .resource_mut::<EditorStatus>()
+        .info(format!("Opened level {}", path.display()));
+    world.resource_mut::<CurrentLevel>().path = Some(path);
+    remove_recovery_file(world);
+}
+// Despawn every editor object and forget everything that referred to them
+pub fn clear_level(world: &mut World) {
+    let roots: Vec<Entity> = world
+        .query_filtered::<Entity, (With<IncludeInSave>, Without<EditorChildOf>)>()
+        .iter(world)
+        .collect();
+    for root in roots {
+        world.entity_mut(root).despawn();
+    }
+    world.resource_mut::<EditorSelected>().clear();
+    world.resource_mut::<CurrentObjectManipulationMode>().mode = ManipulationMode::None;
+    *world.resource_mut::<EditorHistory>() = EditorHistory::default();
+}
+pub fn confirm_discard_changes(world: &World) -> bool {
+    if !world.resource::<EditorHistory>().is_dirty() {
+        return true;
+    }
+    MessageDialog::new()
+        .set_title("Unsaved changes")
+        .set_description("The current level has unsaved changes. Discard them?")
+        .set_buttons(MessageButtons::YesNo)
+        .show()
+        == MessageDialogResult::Yes
+}
+/// Show the level name in the window title, with a * when there are unsaved changes
+pub fn update_window_title(
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+    current_level: Res<CurrentLevel>,
+    history: Res<EditorHistory>,
+) {
+    let Ok(mut window) = window_q.single_mut() else {
+        return;
+    };
+    let level_name = current_level
+        .path
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Untitled".to_owned());
+    let dirty_marker = if history.is_dirty() { "*" } else { "" };
+    let title = format!("{}{} - Editor", level_name, dirty_marker);
+    // Only touch the window when the title actually changes
+    if window.title != title {
+        window.title = title;
+    }
+}
+// Drop the glTF scene of objects whose GltfRef was changed, e.g. by relinking a missing asset
+// or undoing that, so finish_loading_scene loads the new one
+pub fn reload_changed_gltf_refs(
+    changed_gltf_refs: Query<
+        (Entity, Ref<GltfRef>, Option<&Children>),
+        (Changed<GltfRef>, With<FinishedGltfRefLoading>),
+    >,
+    links_q: Query<(), With<EditorEntityLink>>,
+    mut commands: Commands,
+) {
+    for (editor_entity, gltf_ref, children) in &changed_gltf_refs {
+        // Just spawned, its glTF scene is the one for this GltfRef already
+        if gltf_ref.is_added() {
+            continue;
+        }
+        for child in children.into_iter().flatten() {
+            if links_q.contains(*child) {
+                commands.entity(*child).despawn();
+            }
+        }
+        commands
+            .entity(editor_entity)
+            .remove::<(FinishedGltfRefLoading, MissingAsset)>();
+    }
 }
 pub fn finish_loading_scene(
     unloaded_gltf_refs: Query<(Entity, &GltfRef), Without<FinishedGltfRefLoading>>,
@@ -59,84 +582,291 @@ pub fn finish_loading_scene(
     mut commands: Commands,
 ) {
     for (editor_entity, gltf_ref) in unloaded_gltf_refs {
-        let gltf =
-            asset_server.load(GltfAssetLabel::Scene(0).from_asset(gltf_ref.asset_path.clone()));
-        let _gltf_entity = commands
-            .spawn((
-                SceneRoot(gltf),
-                EditorEntityLink(editor_entity),
-                ChildOf(editor_entity),
-            ))
-            .id();
+        let mut gltf_entity = commands.spawn((
+            EditorEntityLink(editor_entity),
+            GltfFile(asset_server.load(gltf_ref.asset_path.clone())),
+            ChildOf(editor_entity),
+        ));
+        load_gltf_ref(
+            &asset_server,
+            &gltf_ref.asset_path,
+            gltf_ref.label.as_deref(),
+        )
+        .insert_into(&mut gltf_entity);
         commands
             .entity(editor_entity)
             .insert(FinishedGltfRefLoading);
     }
 }
 
-pub fn load_gltf_instances(ron_path: &str) -> Vec<EditorGltfInstance> {
-    let file_path = format!("{}/assets/{}", env!("CARGO_MANIFEST_DIR"), ron_path);
-    let data = fs::read_to_string(&file_path)
-        .unwrap_or_else(|e| panic!("Failed to read RON file {}: {}", file_path, e));
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
 
-    ron::from_str(&data).expect("Failed to parse RON file")
-}
-pub fn spawn_gltf_instances(
-    commands: &mut Commands,
-    instances: Vec<EditorGltfInstance>,
-    mut gltf_instances: ResMut<EditorGltfInstances>,
-    asset_server: Res<AssetServer>,
-) {
-    for (index, instance) in instances.into_iter().enumerate() {
-        let EditorGltfInstance {
-            path,
-            transform,
-            parent,
-        } = instance;
-        let path = Path::new(&path);
-        match strip_assets_prefix(path) {
-            Some(relative_path) => {
-                println!("Relative path inside assets: {}", relative_path.display());
-                let editor_entity = commands
-                    .spawn((
-                        Name::new(format!("test glb {}", index)),
-                        GltfRef {
-                            asset_path: relative_path.to_string_lossy().to_string(),
-                            label: None,
-                        },
-                        Transform::from(transform),
-                        Visibility::Visible,
-                        IncludeInSave,
-                    ))
-                    .id();
-
-                gltf_instances.0.push(InstantiatedGltfInstance {
-                    path: path.to_string_lossy().to_string(),
-                    entity: editor_entity,
-                    parent,
-                });
-            }
-            None => {
-                eprintln!("Selected file is not inside the assets folder!");
-            }
-        }
+    use common::{InteractableType, asset_roots::AssetRoots, level::LevelError};
+
+    use super::*;
+    use crate::test_helpers::{editor_objects, set_editor_parent, spawn_object, test_app};
+
+    // "Root" > "Child" > "Grandchild", and a separate "Door" with a gameplay component
+    fn spawn_test_level(world: &mut World) {
+        let root = spawn_object(
+            world,
+            "Root",
+            Transform::from_xyz(1.0, 2.0, -3.5).with_rotation(Quat::from_rotation_y(0.7)),
+        );
+        world.entity_mut(root).insert(GltfRef {
+            asset_path: "models/test.glb".to_owned(),
+            label: Some("Scene0".to_owned()),
+        });
+        let child = spawn_object(
+            world,
+            "Child",
+            Transform::from_xyz(0.25, 0.0, 4.0).with_scale(Vec3::new(2.0, 0.5, 1.0)),
+        );
+        set_editor_parent(world, child, root);
+        let grandchild = spawn_object(
+            world,
+            "Grandchild",
+            Transform::from_rotation(Quat::from_euler(EulerRot::XYZ, 0.3, -1.2, 2.0)),
+        );
+        world.entity_mut(grandchild).insert(GltfRef {
+            asset_path: "shared://props/crate.glb".to_owned(),
+            label: None,
+        });
+        set_editor_parent(world, grandchild, child);
+        let door = spawn_object(world, "Door", Transform::from_xyz(-7.0, 0.0, 0.1));
+        world.entity_mut(door).insert((
+            GltfRef {
+                asset_path: "models/door.glb".to_owned(),
+                label: None,
+            },
+            Interactable {
+                kind: InteractableType::Door,
+            },
+        ));
     }
-    apply_gltf_hierarchy(commands, gltf_instances);
-}
-pub fn apply_gltf_hierarchy(commands: &mut Commands, gltf_instances: ResMut<EditorGltfInstances>) {
-    for (i, instance) in gltf_instances.0.iter().enumerate() {
-        if let Some(parent_index) = instance.parent {
-            dbg!(parent_index);
-            let child = instance.entity;
-            dbg!(child);
-            if let Some(parent_instance) = gltf_instances.0.get(parent_index) {
-                dbg!(parent_instance.entity);
-                let parent = parent_instance.entity;
-                commands.entity(parent).add_child(child);
-                commands.entity(child).insert(EditorChildOf(parent));
-            } else {
-                eprintln!("Invalid parent index {} for instance {}", parent_index, i);
-            }
+
+    fn temp_level_path(test_name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "editor_{}_{}.{}",
+            test_name,
+            std::process::id(),
+            LEVEL_FILE_EXTENSION
+        ))
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut app = test_app();
+        spawn_test_level(app.world_mut());
+        app.update();
+        let before = editor_objects(app.world_mut());
+        assert_eq!(before.len(), 4);
+
+        let path = temp_level_path("round_trip");
+        save_level(app.world_mut(), path.clone());
+        assert!(!app.world().resource::<EditorHistory>().is_dirty());
+
+        // Load into a fresh app so nothing from the saved world can leak into the result
+        let mut loaded_app = test_app();
+        open_level(loaded_app.world_mut(), path.clone());
+        loaded_app.update();
+        let after = editor_objects(loaded_app.world_mut());
+        assert_eq!(
+            loaded_app.world().resource::<CurrentLevel>().path.as_ref(),
+            Some(&path)
+        );
+        fs::remove_file(&path).ok();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn saving_twice_writes_the_same_file() {
+        let mut app = test_app();
+        spawn_test_level(app.world_mut());
+        app.update();
+        let first_path = temp_level_path("first_save");
+        save_level(app.world_mut(), first_path.clone());
+        open_level(app.world_mut(), first_path.clone());
+        app.update();
+        let second_path = temp_level_path("second_save");
+        save_level(app.world_mut(), second_path.clone());
+
+        let first = LevelFile::from_ron(
+            &fs::read_to_string(&first_path).unwrap(),
+            &AssetRoots::default(),
+        )
+        .unwrap();
+        let second = LevelFile::from_ron(
+            &fs::read_to_string(&second_path).unwrap(),
+            &AssetRoots::default(),
+        )
+        .unwrap();
+        fs::remove_file(&first_path).ok();
+        fs::remove_file(&second_path).ok();
+        assert_eq!(first.version, CURRENT_LEVEL_VERSION);
+        // Ids are reassigned, so compare everything else by name
+        let strip_ids = |level: &LevelFile| {
+            level
+                .entities
+                .iter()
+                .map(|entity| {
+                    let parent_name = entity.parent.map(|parent| {
+                        level
+                            .entities
+                            .iter()
+                            .find(|other| other.id == parent)
+                            .and_then(|other| other.name.clone())
+                    });
+                    (
+                        entity.name.clone(),
+                        (
+                            parent_name,
+                            entity.transform,
+                            entity.asset.clone(),
+                            entity.components.clone(),
+                        ),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert_eq!(strip_ids(&first), strip_ids(&second));
+    }
+
+    #[test]
+    fn saving_keeps_a_limited_number_of_backups() {
+        let mut app = test_app();
+        spawn_test_level(app.world_mut());
+        app.update();
+        let folder = std::env::temp_dir().join(format!("editor_backups_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("level.ron");
+        for _ in 0..MAX_LEVEL_BACKUPS + 3 {
+            save_level(app.world_mut(), path.clone());
+        }
+        let read_file_names = || {
+            let mut file_names: Vec<String> = fs::read_dir(&folder)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                .collect();
+            file_names.sort();
+            file_names
+        };
+        let file_names = read_file_names();
+
+        // Several saves within one millisecond, later than all of the ones above
+        let same_time = UNIX_EPOCH + std::time::Duration::from_secs(4_000_000_000);
+        for _ in 0..MAX_LEVEL_BACKUPS + 2 {
+            backup_level_at(&path, same_time).unwrap();
         }
+        let same_time_names = read_file_names();
+        fs::remove_dir_all(&folder).ok();
+
+        // The first save had nothing to back up
+        let backups = file_names
+            .iter()
+            .filter(|name| name.starts_with("level.ron.") && name.ends_with(".bak"))
+            .count();
+        assert_eq!(backups, MAX_LEVEL_BACKUPS);
+        assert!(file_names.contains(&"level.ron".to_owned()));
+        // No temporary file is left behind
+        assert_eq!(file_names.len(), MAX_LEVEL_BACKUPS + 1);
+        // The newest of them are kept, the ones with the highest counters
+        let timestamp = utc_timestamp(same_time);
+        let mut expected: Vec<String> = (2..MAX_LEVEL_BACKUPS + 2)
+            .map(|number| format!("level.ron.{}-{:03}.bak", timestamp, number))
+            .chain(["level.ron".to_owned()])
+            .collect();
+        expected.sort();
+        assert_eq!(same_time_names, expected);
+        assert!(
+            !app.world()
+                .resource::<EditorStatus>()
+                .message
+                .as_ref()
+                .unwrap()
+                .is_error
+        );
+    }
+
+    #[test]
+    fn partial_selection_keeps_world_placement() {
+        let mut app = test_app();
+        spawn_test_level(app.world_mut());
+        app.update();
+        let world = app.world_mut();
+        let child = world
+            .query::<(Entity, &Name)>()
+            .iter(world)
+            .find(|(_, name)| name.as_str() == "Child")
+            .map(|(entity, _)| entity)
+            .unwrap();
+        let child_global = world
+            .get::<GlobalTransform>(child)
+            .unwrap()
+            .compute_transform();
+        let subtree = editor_subtrees(world, [child]);
+        assert_eq!(subtree.len(), 2);
+        let level_ron = level_from_world(world, &subtree).to_ron().unwrap();
+
+        let mut pasted_app = test_app();
+        let level = LevelFile::from_ron(&level_ron, &AssetRoots::default()).unwrap();
+        spawn_level(pasted_app.world_mut(), &level);
+        pasted_app.update();
+        let pasted = editor_objects(pasted_app.world_mut());
+        assert_eq!(pasted.len(), 2);
+        assert_eq!(pasted["Child"].parent, None);
+        assert_eq!(pasted["Child"].transform, child_global);
+        assert_eq!(pasted["Grandchild"].parent.as_deref(), Some("Child"));
+    }
+
+    #[test]
+    fn unreadable_level_keeps_the_current_one() {
+        let mut app = test_app();
+        spawn_test_level(app.world_mut());
+        app.update();
+        let before = editor_objects(app.world_mut());
+
+        let path = temp_level_path("newer_version");
+        let newer_level = format!("(version: {}, entities: [])", CURRENT_LEVEL_VERSION + 1);
+        fs::write(&path, newer_level).unwrap();
+        assert!(matches!(
+            LevelFile::from_ron(&fs::read_to_string(&path).unwrap(), &AssetRoots::default()),
+            Err(LevelError::UnsupportedVersion(_))
+        ));
+        open_level(app.world_mut(), path.clone());
+        fs::remove_file(&path).ok();
+
+        assert_eq!(editor_objects(app.world_mut()), before);
+        assert_eq!(app.world().resource::<CurrentLevel>().path, None);
+    }
+
+    #[test]
+    fn loads_the_old_gltf_instance_list() {
+        let mut app = test_app();
+        app.insert_resource(EditorAssetRoots(AssetRoots {
+            base_path: PathBuf::from("/home/someone/game/editor"),
+            extra_roots: Vec::new(),
+        }));
+        let path = temp_level_path("legacy");
+        fs::write(
+            &path,
+            r#"[
+                (path: "/home/someone/game/editor/assets/test.glb", transform: (translation: (1.0, 0.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)), parent: None),
+                (path: "/home/someone/game/editor/assets/models/child.glb", transform: (translation: (0.0, 2.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)), parent: Some(0)),
+            ]"#,
+        )
+        .unwrap();
+        open_level(app.world_mut(), path.clone());
+        fs::remove_file(&path).ok();
+        app.update();
+
+        let loaded = editor_objects(app.world_mut());
+        assert_eq!(loaded.len(), 2);
+        let child = &loaded["test glb 1"];
+        assert_eq!(child.parent.as_deref(), Some("test glb 0"));
+        assert_eq!(child.gltf_ref, Some(("models/child.glb".to_owned(), None)));
+        assert_eq!(child.transform, Transform::from_xyz(0.0, 2.0, 0.0));
     }
 }
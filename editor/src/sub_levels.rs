@@ -0,0 +1,363 @@
+use std::fs;
+
+use bevy::prelude::*;
+use common::level::{LevelEntity, LevelFile, SubLevelRef};
+use rfd::FileDialog;
+
+use crate::{
+    camera::EditorCamera,
+    defs::{EditorAssetRoots, EditorChildOf, EditorSelected, EditorStatus, SubLevelRoot},
+    editor_history::{EditorEdit, EditorHistory},
+    saving_loading::{
+        backup_level, confirm_discard_changes, editor_subtrees, level_from_world_relative_to,
+        level_transform, open_level, spawn_level_below, spawn_level_entity, write_atomically,
+    },
+};
+const LEVEL_FILE_EXTENSION: &str = "ron";
+// Where the streaming radius starts when a sub-level is first set to stream
+pub const DEFAULT_LOAD_DISTANCE: f32 = 100.0;
+
+/// The root of the innermost sub-level an object is saved to, None for objects of the level
+/// itself. A sub-level root belongs to the level it's placed in, not to its own file
+pub fn sub_level_of(world: &World, entity: Entity) -> Option<Entity> {
+    let mut current = world.get::<EditorChildOf>(entity)?.0;
+    loop {
+        if world.get::<SubLevelRoot>(current).is_some() {
+            return Some(current);
+        }
+        current = world.get::<EditorChildOf>(current)?.0;
+    }
+}
+/// The contents of a sub-level as they're written to its file, relative to its root
+pub fn sub_level_from_world(world: &World, root: Entity) -> LevelFile {
+    let children: Vec<Entity> = world
+        .get::<Children>(root)
+        .map(|children| children.iter().collect())
+        .unwrap_or_default();
+    let entities = editor_subtrees(world, children);
+    level_from_world_relative_to(world, &entities, Some(root))
+}
+/// Spawn a sub-level's root and the contents of its file below it, returning them root first.
+/// When the file can't be read only the root is spawned, saving writes a new file then
+pub fn spawn_sub_level(
+    world: &mut World,
+    level_entity: &LevelEntity,
+    sub_level: &SubLevelRef,
+    parent: Option<Entity>,
+) -> Vec<Entity> {
+    let root = spawn_level_entity(world, level_entity, parent);
+    // Inserted before the contents are spawned so a copy of this sub-level inside them finds it
+    // among its ancestors, the saved state is filled in once they're there
+    world.entity_mut(root).insert(SubLevelRoot {
+        path: sub_level.path.clone(),
+        load_distance: sub_level.load_distance,
+        saved_file: String::new(),
+        saved_level: String::new(),
+    });
+    let mut spawned = vec![root];
+    let file_path = world
+        .resource::<EditorAssetRoots>()
+        .file_path(&sub_level.path);
+    // A sub-level that contains itself would never stop loading. Ancestors are spawned
+    // before their contents, so the whole chain is already there
+    let mut ancestor = parent;
+    let mut nested_in_itself = false;
+    while let Some(entity) = ancestor {
+        if world
+            .get::<SubLevelRoot>(entity)
+            .is_some_and(|ancestor_sub_level| ancestor_sub_level.path == sub_level.path)
+        {
+            nested_in_itself = true;
+        }
+        ancestor = world.get::<EditorChildOf>(entity).map(|parent| parent.0);
+    }
+    let saved_file = match (file_path, nested_in_itself) {
+        (_, true) => Err("it's placed inside itself".to_owned()),
+        (None, false) => Err("unknown asset root".to_owned()),
+        (Some(file_path), false) => fs::read_to_string(file_path).map_err(|e| e.to_string()),
+    };
+    let contents = saved_file.as_ref().map_err(Clone::clone).and_then(|file| {
+        LevelFile::from_ron(file, world.resource::<EditorAssetRoots>()).map_err(|e| e.to_string())
+    });
+    match contents {
+        Ok(contents) => spawned.extend(spawn_level_below(world, &contents, Some(root))),
+        Err(e) => {
+            world
+                .resource_mut::<EditorStatus>()
+                .error(format!("Error loading sub-level {}: {}", sub_level.path, e));
+        }
+    }
+    let saved_level = sub_level_from_world(world, root)
+        .to_ron()
+        .unwrap_or_default();
+    let mut saved_sub_level = world.get_mut::<SubLevelRoot>(root).unwrap();
+    saved_sub_level.saved_file = saved_file.unwrap_or_default();
+    saved_sub_level.saved_level = saved_level;
+    spawned
+}
+/// Write every sub-level whose contents changed to its file, returning what went wrong
+pub fn save_sub_levels(world: &mut World) -> Vec<String> {
+    let roots: Vec<Entity> = world
+        .query_filtered::<Entity, With<SubLevelRoot>>()
+        .iter(world)
+        .collect();
+    let mut problems = Vec::new();
+    for root in roots {
+        let sub_level = world.get::<SubLevelRoot>(root).unwrap().clone();
+        let level_ron = match sub_level_from_world(world, root).to_ron() {
+            Ok(level_ron) => level_ron,
+            Err(e) => {
+                problems.push(format!(
+                    "serializing sub-level {} failed: {}",
+                    sub_level.path, e
+                ));
+                continue;
+            }
+        };
+        if level_ron == sub_level.saved_level {
+            continue;
+        }
+        let Some(file_path) = world
+            .resource::<EditorAssetRoots>()
+            .file_path(&sub_level.path)
+        else {
+            problems.push(format!(
+                "sub-level {} has an unknown asset root",
+                sub_level.path
+            ));
+            continue;
+        };
+        let on_disk = fs::read_to_string(&file_path).unwrap_or_default();
+        if on_disk != sub_level.saved_file {
+            problems.push(format!(
+                "sub-level {} was changed by someone else, open it individually to merge",
+                sub_level.path
+            ));
+            continue;
+        }
+        if let Err(e) = backup_level(&file_path) {
+            problems.push(format!(
+                "backing up sub-level {} failed: {}",
+                sub_level.path, e
+            ));
+        }
+        if let Err(e) = write_atomically(&file_path, &level_ron) {
+            problems.push(format!(
+                "writing sub-level {} failed: {}",
+                sub_level.path, e
+            ));
+            continue;
+        }
+        let mut saved_sub_level = world.get_mut::<SubLevelRoot>(root).unwrap();
+        saved_sub_level.saved_file = level_ron.clone();
+        saved_sub_level.saved_level = level_ron;
+    }
+    problems
+}
+
+/// Pick a level file and place it as a sub-level in front of the camera
+pub fn add_sub_level_system(world: &mut World) {
+    let asset_roots = world.resource::<EditorAssetRoots>();
+    let Some(path) = FileDialog::new()
+        .add_filter("Level", &[LEVEL_FILE_EXTENSION])
+        .set_directory(asset_roots.default_root_path())
+        .pick_file()
+    else {
+        return;
+    };
+    let Some(asset_path) = asset_roots.asset_path(&path) else {
+        world.resource_mut::<EditorStatus>().error(format!(
+            "Sub-levels have to be inside an asset folder, {} isn't",
+            path.display()
+        ));
+        return;
+    };
+    let transform = world
+        .query_filtered::<&GlobalTransform, With<EditorCamera>>()
+        .single(world)
+        .map(GlobalTransform::compute_transform)
+        .unwrap_or_default();
+    let name = path
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().to_string());
+    let root_entity = LevelEntity {
+        id: 0,
+        name,
+        parent: None,
+        transform: level_transform(&transform),
+        asset: None,
+        components: Vec::new(),
+        prefab: None,
+        sub_level: None,
+    };
+    let sub_level = SubLevelRef {
+        path: asset_path,
+        load_distance: None,
+    };
+    let root = spawn_sub_level(world, &root_entity, &sub_level, None)[0];
+    world
+        .resource_mut::<EditorHistory>()
+        .push(EditorEdit::Spawn {
+            root,
+            snapshot: Vec::new(),
+        });
+    world.resource_mut::<EditorSelected>().select_only(root);
+}
+/// Open the file of the selected sub-level, or of the one the selection is in, as the level
+pub fn open_sub_level_system(world: &mut World) {
+    let Some(selected) = world.resource::<EditorSelected>().active() else {
+        return;
+    };
+    let root = if world.get::<SubLevelRoot>(selected).is_some() {
+        Some(selected)
+    } else {
+        sub_level_of(world, selected)
+    };
+    let Some(sub_level) = root.and_then(|root| world.get::<SubLevelRoot>(root)) else {
+        world
+            .resource_mut::<EditorStatus>()
+            .error("The selected object isn't a sub-level or part of one");
+        return;
+    };
+    let Some(file_path) = world
+        .resource::<EditorAssetRoots>()
+        .file_path(&sub_level.path)
+    else {
+        return;
+    };
+    if confirm_discard_changes(world) {
+        open_level(world, file_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use common::asset_roots::AssetRoots;
+
+    use super::*;
+    use crate::{
+        saving_loading::{editor_level, spawn_level},
+        test_helpers::{editor_objects, find_object, test_app, use_temp_asset_roots},
+    };
+
+    // A level with a "Room" sub-level holding a "Chair", the sub-level file is written into a
+    // temporary asset folder the app's asset roots point to
+    fn spawn_sub_level_test_level(app: &mut App, test_name: &str, room_ron: &str) -> PathBuf {
+        let base_path = use_temp_asset_roots(app, test_name);
+        let room_path = room_path(&base_path);
+        fs::create_dir_all(room_path.parent().unwrap()).unwrap();
+        fs::write(&room_path, room_ron).unwrap();
+        let level = LevelFile::from_ron(
+            r#"(version: 4, entities: [
+                (id: 0, name: Some("Room"), transform: (translation: (10.0, 0.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)), sub_level: Some((path: "levels/room.ron"))),
+            ])"#,
+            &AssetRoots::default(),
+        )
+        .unwrap();
+        spawn_level(app.world_mut(), &level);
+        app.update();
+        base_path
+    }
+    fn room_path(base_path: &Path) -> PathBuf {
+        base_path.join("assets/levels/room.ron")
+    }
+    const ROOM_RON: &str = r#"(version: 4, entities: [
+        (id: 0, name: Some("Chair"), transform: (translation: (1.0, 0.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0))),
+    ])"#;
+    fn read_level(world: &World, path: &Path) -> LevelFile {
+        LevelFile::from_ron(
+            &fs::read_to_string(path).unwrap(),
+            world.resource::<EditorAssetRoots>(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn changed_sub_levels_are_written_back() {
+        let mut app = test_app();
+        let base_path = spawn_sub_level_test_level(&mut app, "sub_level_save", ROOM_RON);
+        let room_path = room_path(&base_path);
+        let world = app.world_mut();
+        assert_eq!(
+            editor_objects(world)["Chair"].parent.as_deref(),
+            Some("Room")
+        );
+
+        // Nothing changed, the file is left alone
+        assert!(save_sub_levels(world).is_empty());
+        assert_eq!(fs::read_to_string(&room_path).unwrap(), ROOM_RON);
+
+        let chair = find_object(world, "Chair");
+        world.get_mut::<Transform>(chair).unwrap().translation = Vec3::new(2.0, 0.0, 0.0);
+        assert!(save_sub_levels(world).is_empty());
+
+        // The chair is saved to the sub-level relative to its root, the level only keeps the root
+        let room = read_level(world, &room_path);
+        assert_eq!(room.entities.len(), 1);
+        assert_eq!(room.entities[0].name.as_deref(), Some("Chair"));
+        assert_eq!(room.entities[0].transform.translation, [2.0, 0.0, 0.0]);
+        let level = editor_level(world);
+        assert_eq!(level.entities.len(), 1);
+        assert_eq!(
+            level.entities[0].sub_level.as_ref().unwrap().path,
+            "levels/room.ron"
+        );
+
+        // What was written is the new baseline, saving again doesn't report a conflict
+        world.get_mut::<Transform>(chair).unwrap().translation = Vec3::new(3.0, 0.0, 0.0);
+        assert!(save_sub_levels(world).is_empty());
+        assert_eq!(
+            read_level(world, &room_path).entities[0]
+                .transform
+                .translation,
+            [3.0, 0.0, 0.0]
+        );
+        fs::remove_dir_all(&base_path).ok();
+    }
+
+    #[test]
+    fn sub_levels_changed_on_disk_are_not_overwritten() {
+        let mut app = test_app();
+        let base_path = spawn_sub_level_test_level(&mut app, "sub_level_conflict", ROOM_RON);
+        let room_path = room_path(&base_path);
+        let world = app.world_mut();
+        let someone_elses_room = ROOM_RON.replace("Chair", "Stool");
+        fs::write(&room_path, &someone_elses_room).unwrap();
+
+        let chair = find_object(world, "Chair");
+        world.get_mut::<Transform>(chair).unwrap().translation = Vec3::new(2.0, 0.0, 0.0);
+        let problems = save_sub_levels(world);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("changed by someone else"));
+        assert_eq!(fs::read_to_string(&room_path).unwrap(), someone_elses_room);
+        fs::remove_dir_all(&base_path).ok();
+    }
+
+    #[test]
+    fn sub_levels_placed_inside_themselves_stop_loading() {
+        let mut app = test_app();
+        let base_path = spawn_sub_level_test_level(
+            &mut app,
+            "sub_level_nested",
+            r#"(version: 4, entities: [
+                (id: 0, name: Some("Inner room"), sub_level: Some((path: "levels/room.ron"))),
+            ])"#,
+        );
+        let room_path = room_path(&base_path);
+        let world = app.world_mut();
+
+        // The inner copy is spawned as an empty root instead of loading the file again
+        let objects = editor_objects(world);
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects["Inner room"].parent.as_deref(), Some("Room"));
+        let inner_room = find_object(world, "Inner room");
+        assert!(world.get::<SubLevelRoot>(inner_room).is_some());
+        assert!(world.get::<Children>(inner_room).is_none());
+        let status = world.resource::<EditorStatus>().message.as_ref().unwrap();
+        assert!(status.is_error);
+        assert!(status.text.contains("inside itself"));
+        fs::remove_dir_all(&base_path).ok();
+    }
+}
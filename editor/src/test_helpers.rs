@@ -0,0 +1,116 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use bevy::{asset::AssetPlugin, prelude::*, transform::TransformPlugin};
+use common::{asset_roots::AssetRoots, level::LevelComponent};
+
+use crate::{
+    defs::{
+        CurrentLevel, CurrentObjectManipulationMode, EditorAssetRoots, EditorChildOf,
+        EditorSelected, EditorStatus, GltfRef, IncludeInSave, RotationEditState,
+    },
+    editor_history::EditorHistory,
+    saving_loading::gameplay_components,
+};
+
+// Headless app with just what saving and loading touch
+pub fn test_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), TransformPlugin))
+        .insert_resource(CurrentLevel::default())
+        .insert_resource(EditorHistory::default())
+        .insert_resource(EditorSelected::default())
+        .insert_resource(CurrentObjectManipulationMode::default())
+        .insert_resource(EditorStatus::default())
+        .insert_resource(RotationEditState::default())
+        .insert_resource(EditorAssetRoots(AssetRoots::default()));
+    app
+}
+// Point the app's asset roots at an empty folder in the temp dir, the test removes it when done
+pub fn use_temp_asset_roots(app: &mut App, test_name: &str) -> PathBuf {
+    let base_path =
+        std::env::temp_dir().join(format!("editor_{}_{}", test_name, std::process::id()));
+    fs::remove_dir_all(&base_path).ok();
+    fs::create_dir_all(base_path.join("assets")).unwrap();
+    app.insert_resource(EditorAssetRoots(AssetRoots {
+        base_path: base_path.clone(),
+        extra_roots: Vec::new(),
+    }));
+    base_path
+}
+pub fn spawn_object(world: &mut World, name: &str, transform: Transform) -> Entity {
+    world
+        .spawn((
+            Name::new(name.to_owned()),
+            transform,
+            Visibility::Visible,
+            IncludeInSave,
+        ))
+        .id()
+}
+pub fn set_editor_parent(world: &mut World, child: Entity, parent: Entity) {
+    world
+        .entity_mut(child)
+        .insert((EditorChildOf(parent), ChildOf(parent)));
+}
+#[derive(Debug, PartialEq)]
+pub struct SavedObject {
+    pub transform: Transform,
+    pub parent: Option<String>,
+    pub gltf_ref: Option<(String, Option<String>)>,
+    pub gameplay: Vec<LevelComponent>,
+}
+// Every editor object by name, with its parent by name since entity ids change on load
+pub fn editor_objects(world: &mut World) -> BTreeMap<String, SavedObject> {
+    let mut objects_q = world.query_filtered::<(
+        Entity,
+        &Name,
+        &Transform,
+        Option<&EditorChildOf>,
+        Option<&GltfRef>,
+    ), With<IncludeInSave>>();
+    let objects: Vec<_> = objects_q
+        .iter(world)
+        .map(|(entity, name, transform, parent, gltf_ref)| {
+            (
+                entity,
+                name.to_string(),
+                *transform,
+                parent.map(|parent| parent.0),
+                gltf_ref.map(|gltf_ref| (gltf_ref.asset_path.clone(), gltf_ref.label.clone())),
+                gameplay_components(world.entity(entity)),
+            )
+        })
+        .collect();
+    objects
+        .into_iter()
+        .map(|(entity, name, transform, parent, gltf_ref, gameplay)| {
+            // The bevy hierarchy has to match the editor one
+            assert_eq!(
+                world
+                    .get::<ChildOf>(entity)
+                    .map(|child_of| child_of.parent()),
+                parent,
+                "ChildOf of {} doesn't match EditorChildOf",
+                name
+            );
+            let parent = parent.map(|parent| world.get::<Name>(parent).unwrap().to_string());
+            (
+                name,
+                SavedObject {
+                    transform,
+                    parent,
+                    gltf_ref,
+                    gameplay,
+                },
+            )
+        })
+        .collect()
+}
+pub fn find_object(world: &mut World, name: &str) -> Entity {
+    world
+        .query::<(Entity, &Name)>()
+        .iter(world)
+        .find(|(_, object_name)| object_name.as_str() == name)
+        .map(|(entity, _)| entity)
+        .unwrap()
+}
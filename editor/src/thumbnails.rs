@@ -0,0 +1,481 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    camera::{RenderTarget, primitives::Aabb, visibility::RenderLayers},
+    image::{CompressedImageFormats, ImageSampler, ImageType},
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::screenshot::{Screenshot, ScreenshotCaptured},
+    },
+    scene::SceneInstanceReady,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+
+use crate::{defs::EditorAssetRoots, helper_funcs::aabb_world_corners};
+// Width and height of the rendered thumbnails in pixels
+const THUMBNAIL_SIZE: u32 = 128;
+// Render layer only the thumbnail camera sees, so the level and the editor camera stay separate
+const THUMBNAIL_LAYER: usize = 1;
+// Far away from the level, so picking and cursor ray casts never reach the scene being rendered
+const THUMBNAIL_ORIGIN: Vec3 = Vec3::new(0.0, -10_000.0, 0.0);
+// Folder next to `assets` the rendered thumbnails are stored in
+const THUMBNAIL_CACHE_FOLDER: &str = ".editor_cache/thumbnails";
+// Frames to wait after framing the camera, for textures to get uploaded
+const THUMBNAIL_SETTLE_FRAMES: u32 = 5;
+// Give up on a glTF that hasn't rendered after this many frames
+const THUMBNAIL_TIMEOUT_FRAMES: u32 = 600;
+// Looking up a cached thumbnail reads the whole glTF to hash it, so only a few run at once
+const THUMBNAIL_LOOKUPS_IN_FLIGHT: usize = 4;
+
+pub struct ThumbnailPlugin;
+impl Plugin for ThumbnailPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Thumbnails::default())
+            .add_observer(prepare_thumbnail_scene)
+            .add_systems(
+                Update,
+                (
+                    forget_changed_thumbnails,
+                    process_thumbnail_queue,
+                    update_thumbnail_job,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Thumbnails of the glTFs shown in the asset browser, rendered one at a time in the
+/// background and cached on disk
+#[derive(Resource, Default)]
+pub struct Thumbnails {
+    entries: HashMap<String, Thumbnail>,
+    queue: VecDeque<String>,
+    // Cache lookups running on the IO task pool, None when the glTF couldn't be read
+    lookups: Vec<(String, Task<Option<CacheLookup>>)>,
+    // glTFs without a cached thumbnail, with the cache file to write theirs to
+    to_render: VecDeque<(String, PathBuf)>,
+    job: Option<ThumbnailJob>,
+    // Created with the first job, so it never becomes the camera egui draws on
+    camera: Option<Entity>,
+    target: Handle<Image>,
+}
+enum Thumbnail {
+    Queued,
+    // Waiting to be uploaded to egui, which needs the egui context
+    Image(egui::ColorImage),
+    Texture(egui::TextureHandle),
+    Failed,
+}
+struct ThumbnailJob {
+    asset_path: String,
+    cache_file: PathBuf,
+    scene: Handle<Scene>,
+    scene_root: Entity,
+    scene_ready: bool,
+    frames: u32,
+    framed_at: Option<u32>,
+    screenshot: Option<Entity>,
+}
+// Root of the glTF scene a thumbnail is being rendered of
+#[derive(Component)]
+struct ThumbnailScene;
+
+impl Thumbnails {
+    /// The thumbnail of a glTF, queueing it to be loaded or rendered if it isn't yet
+    pub fn texture(&mut self, ctx: &egui::Context, asset_path: &str) -> Option<egui::TextureId> {
+        let Some(thumbnail) = self.entries.get_mut(asset_path) else {
+            self.entries
+                .insert(asset_path.to_owned(), Thumbnail::Queued);
+            self.queue.push_back(asset_path.to_owned());
+            return None;
+        };
+        if let Thumbnail::Image(image) = thumbnail {
+            let texture = ctx.load_texture(
+                format!("thumbnail {}", asset_path),
+                std::mem::take(image),
+                egui::TextureOptions::LINEAR,
+            );
+            *thumbnail = Thumbnail::Texture(texture);
+        }
+        match thumbnail {
+            Thumbnail::Texture(texture) => Some(texture.id()),
+            _ => None,
+        }
+    }
+    /// Forget every thumbnail, so files that changed on disk get looked up again
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.queue.clear();
+        self.lookups.clear();
+        self.to_render.clear();
+    }
+}
+
+struct CacheLookup {
+    cache_file: PathBuf,
+    image: Option<egui::ColorImage>,
+}
+fn look_up_cached_thumbnail(file_path: &Path, cache_folder: &Path) -> Option<CacheLookup> {
+    let cache_file = cache_folder.join(cache_file_name(file_path)?);
+    let image = load_cached_thumbnail(&cache_file);
+    Some(CacheLookup { cache_file, image })
+}
+// Cached thumbnails are named after the file contents and modification time, so a changed
+// file never shows a stale one and copies of a glTF share theirs
+fn cache_file_name(file_path: &Path) -> Option<String> {
+    let modified = fs::metadata(file_path)
+        .ok()?
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    let contents = fs::read(file_path).ok()?;
+    Some(format!("{:016x}-{}.png", fnv1a(&contents), modified))
+}
+// 64 bit FNV-1a, unlike std's hashers it's the same in every build so the cache stays valid
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+fn load_cached_thumbnail(cache_file: &Path) -> Option<egui::ColorImage> {
+    let bytes = fs::read(cache_file).ok()?;
+    let image = Image::from_buffer(
+        &bytes,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        true,
+        ImageSampler::Default,
+        RenderAssetUsages::default(),
+    )
+    .ok()?;
+    let rgba = image.try_into_dynamic().ok()?.to_rgba8();
+    Some(egui::ColorImage::from_rgba_unmultiplied(
+        [rgba.width() as usize, rgba.height() as usize],
+        rgba.as_raw(),
+    ))
+}
+fn thumbnail_target() -> Image {
+    let size = Extent3d {
+        width: THUMBNAIL_SIZE,
+        height: THUMBNAIL_SIZE,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+        | TextureUsages::COPY_DST
+        | TextureUsages::COPY_SRC
+        | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+// A reloaded glTF gets a new thumbnail the next time the browser shows it
+fn forget_changed_thumbnails(
+    mut scene_events: MessageReader<AssetEvent<Scene>>,
+    asset_server: Res<AssetServer>,
+    mut thumbnails: ResMut<Thumbnails>,
+) {
+    for event in scene_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        if let Some(path) = asset_server.get_path(*id) {
+            thumbnails.entries.remove(&path.without_label().to_string());
+        }
+    }
+}
+// Look up cached thumbnails in the background, and start rendering the first glTF that
+// doesn't have one
+fn process_thumbnail_queue(
+    mut thumbnails: ResMut<Thumbnails>,
+    asset_roots: Res<EditorAssetRoots>,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut commands: Commands,
+) {
+    let thumbnails = &mut *thumbnails;
+    let cache_folder = asset_roots.base_path.join(THUMBNAIL_CACHE_FOLDER);
+    while thumbnails.lookups.len() < THUMBNAIL_LOOKUPS_IN_FLIGHT {
+        let Some(asset_path) = thumbnails.queue.pop_front() else {
+            break;
+        };
+        let Some(file_path) = asset_roots.file_path(&asset_path) else {
+            thumbnails.entries.insert(asset_path, Thumbnail::Failed);
+            continue;
+        };
+        let cache_folder = cache_folder.clone();
+        let lookup = IoTaskPool::get()
+            .spawn(async move { look_up_cached_thumbnail(&file_path, &cache_folder) });
+        thumbnails.lookups.push((asset_path, lookup));
+    }
+    let mut lookups = std::mem::take(&mut thumbnails.lookups);
+    lookups.retain_mut(|(asset_path, lookup)| {
+        let Some(lookup) = block_on(future::poll_once(lookup)) else {
+            return true;
+        };
+        let asset_path = std::mem::take(asset_path);
+        match lookup {
+            Some(CacheLookup {
+                image: Some(image), ..
+            }) => {
+                thumbnails
+                    .entries
+                    .insert(asset_path, Thumbnail::Image(image));
+            }
+            Some(CacheLookup { cache_file, .. }) => {
+                thumbnails.to_render.push_back((asset_path, cache_file));
+            }
+            None => {
+                thumbnails.entries.insert(asset_path, Thumbnail::Failed);
+            }
+        }
+        false
+    });
+    thumbnails.lookups = lookups;
+
+    if thumbnails.job.is_some() {
+        return;
+    }
+    let Some((asset_path, cache_file)) = thumbnails.to_render.pop_front() else {
+        return;
+    };
+    if thumbnails.camera.is_none() {
+        thumbnails.target = images.add(thumbnail_target());
+        let camera = commands
+            .spawn((
+                Name::new("Thumbnail Camera"),
+                Camera3d::default(),
+                Camera {
+                    target: RenderTarget::Image(thumbnails.target.clone().into()),
+                    is_active: false,
+                    order: -1,
+                    clear_color: ClearColorConfig::Custom(Color::srgb(0.2, 0.2, 0.22)),
+                    ..default()
+                },
+                // Same environment map as the editor camera, without any other lights
+                EnvironmentMapLight {
+                    diffuse_map: asset_server
+                        .load("environment_maps/pisa_diffuse_rgb9e5_zstd.ktx2"),
+                    specular_map: asset_server
+                        .load("environment_maps/pisa_specular_rgb9e5_zstd.ktx2"),
+                    intensity: 2000.0,
+                    ..default()
+                },
+                Msaa::Off,
+                RenderLayers::layer(THUMBNAIL_LAYER),
+                Transform::from_translation(THUMBNAIL_ORIGIN),
+            ))
+            .id();
+        thumbnails.camera = Some(camera);
+    }
+    let scene = asset_server.load(GltfAssetLabel::Scene(0).from_asset(asset_path.clone()));
+    // Hidden until its meshes are moved to the thumbnail layer
+    let scene_root = commands
+        .spawn((
+            Name::new("Thumbnail Scene"),
+            ThumbnailScene,
+            SceneRoot(scene.clone()),
+            Transform::from_translation(THUMBNAIL_ORIGIN),
+            Visibility::Hidden,
+        ))
+        .id();
+    thumbnails.job = Some(ThumbnailJob {
+        asset_path,
+        cache_file,
+        scene,
+        scene_root,
+        scene_ready: false,
+        frames: 0,
+        framed_at: None,
+        screenshot: None,
+    });
+}
+
+fn prepare_thumbnail_scene(
+    trigger: On<SceneInstanceReady>,
+    scene_q: Query<(), With<ThumbnailScene>>,
+    children_q: Query<&Children>,
+    mut thumbnails: ResMut<Thumbnails>,
+    mut commands: Commands,
+) {
+    let scene_root = trigger.entity;
+    if !scene_q.contains(scene_root) {
+        return;
+    }
+    // RenderLayers isn't inherited, every mesh needs its own
+    for descendant in children_q.iter_descendants(scene_root) {
+        commands
+            .entity(descendant)
+            .insert(RenderLayers::layer(THUMBNAIL_LAYER));
+    }
+    commands.entity(scene_root).insert(Visibility::Inherited);
+    if let Some(job) = thumbnails
+        .job
+        .as_mut()
+        .filter(|job| job.scene_root == scene_root)
+    {
+        job.scene_ready = true;
+    }
+}
+
+// Frame the camera on the loaded scene, then capture it once everything has been uploaded
+fn update_thumbnail_job(
+    mut thumbnails: ResMut<Thumbnails>,
+    asset_server: Res<AssetServer>,
+    children_q: Query<&Children>,
+    mesh_bounds_q: Query<(Option<&Aabb>, &GlobalTransform), With<Mesh3d>>,
+    mut camera_q: Query<(&mut Camera, &mut Transform, &mut Projection)>,
+    mut commands: Commands,
+) {
+    let thumbnails = &mut *thumbnails;
+    let Some(job) = thumbnails.job.as_mut() else {
+        return;
+    };
+    job.frames += 1;
+    if job.frames > THUMBNAIL_TIMEOUT_FRAMES || asset_server.load_state(&job.scene).is_failed() {
+        let asset_path = job.asset_path.clone();
+        finish_thumbnail_job(thumbnails, Thumbnail::Failed, &mut commands);
+        eprintln!("Couldn't render a thumbnail of {}", asset_path);
+        return;
+    }
+    if !job.scene_ready {
+        return;
+    }
+    let Some(camera_entity) = thumbnails.camera else {
+        return;
+    };
+    let Ok((mut camera, mut cam_transform, mut projection)) = camera_q.get_mut(camera_entity)
+    else {
+        return;
+    };
+
+    let Some(framed_at) = job.framed_at else {
+        let mut min = Vec3::INFINITY;
+        let mut max = Vec3::NEG_INFINITY;
+        for descendant in children_q.iter_descendants(job.scene_root) {
+            let Ok((aabb, mesh_transform)) = mesh_bounds_q.get(descendant) else {
+                continue;
+            };
+            // Bounds are calculated the frame after the meshes are spawned
+            let Some(aabb) = aabb else {
+                return;
+            };
+            for corner in aabb_world_corners(aabb, mesh_transform.affine()) {
+                min = min.min(corner);
+                max = max.max(corner);
+            }
+        }
+        if min.x > max.x {
+            // Nothing to render, e.g. a glTF with only lights or empties
+            finish_thumbnail_job(thumbnails, Thumbnail::Failed, &mut commands);
+            return;
+        }
+        // Far enough back for a sphere around the bounds to fit the view, from above the front
+        let center = (min + max) / 2.0;
+        let radius = ((max - min).length() / 2.0).max(0.01);
+        let fov = match *projection {
+            Projection::Perspective(ref perspective) => perspective.fov,
+            _ => PerspectiveProjection::default().fov,
+        };
+        let distance = radius / (fov / 2.0).sin();
+        *cam_transform =
+            Transform::from_translation(center + Vec3::new(1.0, 0.8, 1.0).normalize() * distance)
+                .looking_at(center, Vec3::Y);
+        *projection = Projection::Perspective(PerspectiveProjection {
+            fov,
+            near: (distance - radius).max(0.001) / 2.0,
+            far: distance + radius * 2.0,
+            ..default()
+        });
+        camera.is_active = true;
+        job.framed_at = Some(job.frames);
+        return;
+    };
+    if job.frames - framed_at < THUMBNAIL_SETTLE_FRAMES
+        || !asset_server.is_loaded_with_dependencies(&job.scene)
+    {
+        return;
+    }
+    if job.screenshot.is_some() {
+        return;
+    }
+    let screenshot = commands
+        .spawn(Screenshot::image(thumbnails.target.clone()))
+        .observe(save_thumbnail)
+        .id();
+    job.screenshot = Some(screenshot);
+}
+
+fn save_thumbnail(
+    trigger: On<ScreenshotCaptured>,
+    mut thumbnails: ResMut<Thumbnails>,
+    mut commands: Commands,
+) {
+    // A screenshot that arrives after its job timed out belongs to no job
+    let Some(job) = thumbnails
+        .job
+        .as_ref()
+        .filter(|job| job.screenshot == Some(trigger.event_target()))
+    else {
+        return;
+    };
+    let Ok(image) = trigger.image.clone().try_into_dynamic() else {
+        finish_thumbnail_job(&mut thumbnails, Thumbnail::Failed, &mut commands);
+        return;
+    };
+    // Still shown if saving fails, it's only rendered again next time the editor starts
+    if let Some(folder) = job.cache_file.parent() {
+        fs::create_dir_all(folder).ok();
+    }
+    if let Err(e) = image.save(&job.cache_file) {
+        eprintln!(
+            "Error saving the thumbnail of {} to {}: {}",
+            job.asset_path,
+            job.cache_file.display(),
+            e
+        );
+    }
+    let rgba = image.to_rgba8();
+    let image = egui::ColorImage::from_rgba_unmultiplied(
+        [rgba.width() as usize, rgba.height() as usize],
+        rgba.as_raw(),
+    );
+    finish_thumbnail_job(&mut thumbnails, Thumbnail::Image(image), &mut commands);
+}
+
+fn finish_thumbnail_job(
+    thumbnails: &mut Thumbnails,
+    thumbnail: Thumbnail,
+    commands: &mut Commands,
+) {
+    let Some(job) = thumbnails.job.take() else {
+        return;
+    };
+    commands.entity(job.scene_root).despawn();
+    if let Some(camera) = thumbnails.camera {
+        commands
+            .entity(camera)
+            .entry::<Camera>()
+            .and_modify(|mut camera| {
+                camera.is_active = false;
+            });
+    }
+    // Cleared while rendering, it will be looked up again when the browser asks for it
+    if let Some(entry) = thumbnails.entries.get_mut(&job.asset_path) {
+        *entry = thumbnail;
+    }
+}
@@ -0,0 +1,433 @@
+use bevy::{
+    color::palettes::css::{BLUE, GREEN, RED, WHITE, YELLOW},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_egui::EguiContexts;
+
+use crate::{
+    camera::{EditorCamera, toggle_cursor_condition},
+    defs::{
+        CurrentObjectManipulationMode, EditorAxis, EditorChildOf, EditorSelected, GizmoHandle,
+        GizmoTool, ManipulationMode, PivotPoint, TransformGizmoState,
+    },
+    execute_editor_commands::EditorCommand,
+    helper_funcs::{axis_direction, top_level_selection},
+};
+// Length of the handles on screen, the gizmo keeps this size at any distance
+const GIZMO_SIZE_PIXELS: f32 = 110.0;
+// How close the cursor has to be to a handle to grab it
+const HANDLE_PICK_DISTANCE: f32 = 8.0;
+const RING_SEGMENTS: usize = 48;
+// Handles start a bit away from the center so they don't all overlap there
+const HANDLE_START: f32 = 0.2;
+const PLANE_HANDLE_MIN: f32 = 0.25;
+const PLANE_HANDLE_MAX: f32 = 0.45;
+const UNIFORM_SCALE_RADIUS: f32 = 0.25;
+const AXES: [EditorAxis; 3] = [EditorAxis::X, EditorAxis::Y, EditorAxis::Z];
+
+pub struct TransformGizmoPlugin;
+
+impl Plugin for TransformGizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TransformGizmoState::default())
+            .init_gizmo_group::<TransformGizmoGroup>()
+            .insert_gizmo_config(
+                TransformGizmoGroup,
+                GizmoConfig {
+                    // Draw on top of the scene so handles behind objects can still be grabbed
+                    depth_bias: -1.0,
+                    line: GizmoLineConfig {
+                        width: 3.0,
+                        ..default()
+                    },
+                    ..default()
+                },
+            )
+            // Runs before the hotkeys so a press on a handle doesn't also confirm the drag
+            .add_systems(
+                Update,
+                (
+                    pick_gizmo_handle.run_if(toggle_cursor_condition),
+                    draw_transform_gizmo,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct TransformGizmoGroup;
+
+// Where the gizmo is drawn, and how long a handle is in world units
+struct GizmoFrame {
+    origin: Vec3,
+    rotation: Quat,
+    size: f32,
+}
+impl GizmoFrame {
+    fn axis(&self, axis: EditorAxis) -> Vec3 {
+        self.rotation * axis_direction(axis)
+    }
+    fn ring_points(&self, rotation: Quat, radius: f32) -> Vec<Vec3> {
+        (0..=RING_SEGMENTS)
+            .map(|segment| {
+                let angle = segment as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                self.origin + rotation * Vec3::new(angle.cos(), angle.sin(), 0.0) * radius
+            })
+            .collect()
+    }
+    fn plane_corners(&self, axis: EditorAxis) -> [Vec3; 4] {
+        let (first, second) = other_axes(axis);
+        let (first, second) = (self.axis(first) * self.size, self.axis(second) * self.size);
+        [
+            (PLANE_HANDLE_MIN, PLANE_HANDLE_MIN),
+            (PLANE_HANDLE_MAX, PLANE_HANDLE_MIN),
+            (PLANE_HANDLE_MAX, PLANE_HANDLE_MAX),
+            (PLANE_HANDLE_MIN, PLANE_HANDLE_MAX),
+        ]
+        .map(|(a, b)| self.origin + first * a + second * b)
+    }
+}
+
+fn gizmo_frame(
+    selected_entity: &EditorSelected,
+    manip_mode: &CurrentObjectManipulationMode,
+    global_transforms: &Query<&GlobalTransform>,
+    editor_childof_query: &Query<&EditorChildOf>,
+    cam_transform: &GlobalTransform,
+    camera: &Camera,
+) -> Option<GizmoFrame> {
+    let active_transform = global_transforms.get(selected_entity.active()?).ok()?;
+    // Same pivot the manipulation itself uses
+    let origin = match manip_mode.pivot {
+        PivotPoint::MedianPoint => {
+            let positions: Vec<Vec3> = top_level_selection(selected_entity, editor_childof_query)
+                .into_iter()
+                .filter_map(|entity| Some(global_transforms.get(entity).ok()?.translation()))
+                .collect();
+            if positions.is_empty() {
+                return None;
+            }
+            positions.iter().sum::<Vec3>() / positions.len() as f32
+        }
+        PivotPoint::ActiveObject => active_transform.translation(),
+    };
+    let rotation = if manip_mode.local {
+        active_transform.rotation()
+    } else {
+        Quat::IDENTITY
+    };
+    // Measure how many pixels one world unit covers at the gizmo's depth
+    let origin_screen = camera.world_to_viewport(cam_transform, origin).ok()?;
+    let unit_screen = camera
+        .world_to_viewport(cam_transform, origin + *cam_transform.right())
+        .ok()?;
+    let pixels_per_unit = origin_screen.distance(unit_screen);
+    if pixels_per_unit <= f32::EPSILON {
+        return None;
+    }
+    Some(GizmoFrame {
+        origin,
+        rotation,
+        size: GIZMO_SIZE_PIXELS / pixels_per_unit,
+    })
+}
+
+// While manipulating, show the handles for the current mode instead of the picked tool
+fn visible_tool(
+    gizmo_state: &TransformGizmoState,
+    manip_mode: &CurrentObjectManipulationMode,
+) -> GizmoTool {
+    match manip_mode.mode {
+        ManipulationMode::Move => GizmoTool::Translate,
+        ManipulationMode::Rotate => GizmoTool::Rotate,
+        ManipulationMode::Scale => GizmoTool::Scale,
+        ManipulationMode::None => gizmo_state.tool,
+    }
+}
+fn tool_handles(tool: GizmoTool) -> Vec<GizmoHandle> {
+    match tool {
+        GizmoTool::Translate => AXES
+            .iter()
+            .map(|axis| GizmoHandle::Axis(*axis))
+            .chain(AXES.iter().map(|axis| GizmoHandle::Plane(*axis)))
+            .collect(),
+        GizmoTool::Rotate => AXES.iter().map(|axis| GizmoHandle::Ring(*axis)).collect(),
+        GizmoTool::Scale => AXES
+            .iter()
+            .map(|axis| GizmoHandle::ScaleAxis(*axis))
+            .chain([GizmoHandle::ScaleUniform])
+            .collect(),
+    }
+}
+fn other_axes(axis: EditorAxis) -> (EditorAxis, EditorAxis) {
+    match axis {
+        EditorAxis::X => (EditorAxis::Y, EditorAxis::Z),
+        EditorAxis::Y => (EditorAxis::X, EditorAxis::Z),
+        EditorAxis::Z => (EditorAxis::X, EditorAxis::Y),
+    }
+}
+fn axis_color(axis: EditorAxis) -> Srgba {
+    match axis {
+        EditorAxis::X => RED,
+        EditorAxis::Y => GREEN,
+        EditorAxis::Z => BLUE,
+    }
+}
+
+fn pick_gizmo_handle(
+    mut gizmo_state: ResMut<TransformGizmoState>,
+    mut egui_ctx: EguiContexts,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    cam_q: Query<(&GlobalTransform, &Camera), With<EditorCamera>>,
+    global_transforms: Query<&GlobalTransform>,
+    editor_childof_query: Query<&EditorChildOf>,
+    selected_entity: Res<EditorSelected>,
+    manip_mode: Res<CurrentObjectManipulationMode>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut cmd_writer: MessageWriter<EditorCommand>,
+) {
+    if gizmo_state.dragging.is_some() {
+        if !mouse_buttons.pressed(MouseButton::Left) {
+            cmd_writer.write(EditorCommand::Confirm);
+            gizmo_state.dragging = None;
+        } else if manip_mode.mode == ManipulationMode::None {
+            // Cancelled with Escape while dragging
+            gizmo_state.dragging = None;
+        }
+        return;
+    }
+    gizmo_state.hovered = None;
+    // Keyboard manipulations have the mouse already
+    if manip_mode.mode != ManipulationMode::None {
+        return;
+    }
+    let Ok(ctx) = egui_ctx.ctx_mut() else {
+        return;
+    };
+    if ctx.wants_pointer_input() {
+        return;
+    }
+    let Ok(window) = window_q.single() else {
+        return;
+    };
+    let Some(cursor_position) = window.cursor_position() else {
+        return;
+    };
+    let Ok((cam_transform, camera)) = cam_q.single() else {
+        return;
+    };
+    let Some(frame) = gizmo_frame(
+        &selected_entity,
+        &manip_mode,
+        &global_transforms,
+        &editor_childof_query,
+        cam_transform,
+        camera,
+    ) else {
+        return;
+    };
+
+    let to_screen = |point: Vec3| camera.world_to_viewport(cam_transform, point).ok();
+    gizmo_state.hovered = tool_handles(gizmo_state.tool)
+        .into_iter()
+        .filter_map(|handle| {
+            let distance =
+                handle_screen_distance(handle, &frame, cam_transform, cursor_position, &to_screen)?;
+            (distance <= HANDLE_PICK_DISTANCE).then_some((handle, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(handle, _)| handle);
+
+    let Some(handle) = gizmo_state.hovered else {
+        return;
+    };
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        // Drive the same manipulation the hotkeys start, restricted by the grabbed handle
+        let (mode, restriction) = match handle {
+            GizmoHandle::Axis(axis) => (
+                ManipulationMode::Move,
+                Some(EditorCommand::RestrictAxis(axis)),
+            ),
+            GizmoHandle::Plane(axis) => (
+                ManipulationMode::Move,
+                Some(EditorCommand::RestrictPlane(axis)),
+            ),
+            GizmoHandle::Ring(axis) => (
+                ManipulationMode::Rotate,
+                Some(EditorCommand::RestrictAxis(axis)),
+            ),
+            GizmoHandle::ScaleAxis(axis) => (
+                ManipulationMode::Scale,
+                Some(EditorCommand::RestrictAxis(axis)),
+            ),
+            GizmoHandle::ScaleUniform => (ManipulationMode::Scale, None),
+        };
+        cmd_writer.write(EditorCommand::SetMode(mode));
+        cmd_writer.write(EditorCommand::BeginTransform);
+        if let Some(restriction) = restriction {
+            cmd_writer.write(restriction);
+        }
+        gizmo_state.dragging = Some(handle);
+    }
+}
+// Distance in pixels from the cursor to a handle, zero when inside a plane handle
+fn handle_screen_distance(
+    handle: GizmoHandle,
+    frame: &GizmoFrame,
+    cam_transform: &GlobalTransform,
+    cursor_position: Vec2,
+    to_screen: &impl Fn(Vec3) -> Option<Vec2>,
+) -> Option<f32> {
+    match handle {
+        GizmoHandle::Axis(axis) | GizmoHandle::ScaleAxis(axis) => {
+            let direction = frame.axis(axis) * frame.size;
+            let start = to_screen(frame.origin + direction * HANDLE_START)?;
+            let end = to_screen(frame.origin + direction)?;
+            Some(distance_to_segment(cursor_position, start, end))
+        }
+        GizmoHandle::Plane(axis) => {
+            let corners = frame
+                .plane_corners(axis)
+                .map(to_screen)
+                .into_iter()
+                .collect::<Option<Vec<Vec2>>>()?;
+            point_in_quad(cursor_position, &corners).then_some(0.0)
+        }
+        GizmoHandle::Ring(axis) => {
+            let rotation = Quat::from_rotation_arc(Vec3::Z, frame.axis(axis));
+            polyline_distance(
+                cursor_position,
+                &frame.ring_points(rotation, frame.size),
+                to_screen,
+            )
+        }
+        GizmoHandle::ScaleUniform => {
+            let points =
+                frame.ring_points(cam_transform.rotation(), frame.size * UNIFORM_SCALE_RADIUS);
+            polyline_distance(cursor_position, &points, to_screen)
+        }
+    }
+}
+fn polyline_distance(
+    cursor_position: Vec2,
+    points: &[Vec3],
+    to_screen: &impl Fn(Vec3) -> Option<Vec2>,
+) -> Option<f32> {
+    let screen_points: Vec<Vec2> = points
+        .iter()
+        .filter_map(|point| to_screen(*point))
+        .collect();
+    screen_points
+        .windows(2)
+        .map(|segment| distance_to_segment(cursor_position, segment[0], segment[1]))
+        .min_by(|a, b| a.total_cmp(b))
+}
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    if length_squared <= f32::EPSILON {
+        return point.distance(start);
+    }
+    let t = ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0);
+    point.distance(start + segment * t)
+}
+// The projected quad is convex, so the point is inside if it's on the same side of every edge
+fn point_in_quad(point: Vec2, corners: &[Vec2]) -> bool {
+    let sides: Vec<f32> = (0..corners.len())
+        .map(|index| {
+            let start = corners[index];
+            let end = corners[(index + 1) % corners.len()];
+            (end - start).perp_dot(point - start)
+        })
+        .collect();
+    sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+}
+
+fn draw_transform_gizmo(
+    mut gizmos: Gizmos<TransformGizmoGroup>,
+    gizmo_state: Res<TransformGizmoState>,
+    cam_q: Query<(&GlobalTransform, &Camera), With<EditorCamera>>,
+    global_transforms: Query<&GlobalTransform>,
+    editor_childof_query: Query<&EditorChildOf>,
+    selected_entity: Res<EditorSelected>,
+    manip_mode: Res<CurrentObjectManipulationMode>,
+) {
+    let Ok((cam_transform, camera)) = cam_q.single() else {
+        return;
+    };
+    let Some(frame) = gizmo_frame(
+        &selected_entity,
+        &manip_mode,
+        &global_transforms,
+        &editor_childof_query,
+        cam_transform,
+        camera,
+    ) else {
+        return;
+    };
+    let highlighted = gizmo_state.dragging.or(gizmo_state.hovered);
+    let color = |handle: GizmoHandle, base: Srgba| {
+        if highlighted == Some(handle) {
+            YELLOW
+        } else {
+            base
+        }
+    };
+    for handle in tool_handles(visible_tool(&gizmo_state, &manip_mode)) {
+        match handle {
+            GizmoHandle::Axis(axis) => {
+                let direction = frame.axis(axis) * frame.size;
+                gizmos
+                    .arrow(
+                        frame.origin + direction * HANDLE_START,
+                        frame.origin + direction,
+                        color(handle, axis_color(axis)),
+                    )
+                    .with_tip_length(frame.size * 0.2);
+            }
+            GizmoHandle::Plane(axis) => {
+                let corners = frame.plane_corners(axis);
+                gizmos.linestrip(
+                    corners.iter().chain([&corners[0]]).copied(),
+                    color(handle, axis_color(axis)),
+                );
+            }
+            GizmoHandle::Ring(axis) => {
+                let rotation = Quat::from_rotation_arc(Vec3::Z, frame.axis(axis));
+                gizmos
+                    .circle(
+                        Isometry3d::new(frame.origin, rotation),
+                        frame.size,
+                        color(handle, axis_color(axis)),
+                    )
+                    .resolution(RING_SEGMENTS as u32);
+            }
+            GizmoHandle::ScaleAxis(axis) => {
+                let direction = frame.axis(axis) * frame.size;
+                let tip = frame.origin + direction;
+                gizmos.line(
+                    frame.origin + direction * HANDLE_START,
+                    tip,
+                    color(handle, axis_color(axis)),
+                );
+                gizmos.cuboid(
+                    Transform::from_translation(tip)
+                        .with_rotation(frame.rotation)
+                        .with_scale(Vec3::splat(frame.size * 0.1)),
+                    color(handle, axis_color(axis)),
+                );
+            }
+            GizmoHandle::ScaleUniform => {
+                gizmos
+                    .circle(
+                        Isometry3d::new(frame.origin, cam_transform.rotation()),
+                        frame.size * UNIFORM_SCALE_RADIUS,
+                        color(handle, WHITE),
+                    )
+                    .resolution(RING_SEGMENTS as u32);
+            }
+        }
+    }
+}
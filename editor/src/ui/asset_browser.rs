@@ -0,0 +1,354 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use bevy::{gltf::Gltf, prelude::*};
+use bevy_egui::EguiContexts;
+use common::asset_roots::AssetRoots;
+use rfd::FileDialog;
+
+use crate::{
+    clipboard::cursor_hit_point,
+    defs::{EditorAssetRoots, EditorSelected, GltfRef, IncludeInSave},
+    editor_history::{EditorEdit, EditorHistory},
+    execute_editor_commands::EditorCommand,
+    gltf_parts::gltf_entries,
+    helper_funcs::asset_path_or_import,
+    thumbnails::Thumbnails,
+};
+// Files the browser lists, the ones that can be placed in a level
+const BROWSER_EXTENSIONS: [&str; 2] = ["glb", "gltf"];
+// Size thumbnails are shown at next to the file names
+const THUMBNAIL_DISPLAY_SIZE: f32 = 48.0;
+
+#[derive(Resource, Default)]
+pub struct AssetBrowser {
+    pub open: bool,
+    search: String,
+    // One folder per asset root, scanned when the browser opens
+    roots: Vec<AssetFolder>,
+    // glTFs whose scenes, nodes and meshes are listed, loaded when their contents are opened
+    contents: HashMap<String, Handle<Gltf>>,
+}
+// Folders and files sorted by name
+#[derive(Default)]
+struct AssetFolder {
+    name: String,
+    folders: Vec<AssetFolder>,
+    files: Vec<AssetFile>,
+}
+struct AssetFile {
+    name: String,
+    asset_path: String,
+}
+/// egui drag-and-drop payload of a glTF, or a scene, node or mesh of one, dragged out of the
+/// browser
+#[derive(Debug, Clone)]
+pub struct DraggedAsset {
+    pub asset_path: String,
+    pub label: Option<String>,
+    pub name: String,
+}
+// What showing a file needs besides the file itself
+struct FileUiContext<'a> {
+    thumbnails: &'a mut Thumbnails,
+    contents: &'a mut HashMap<String, Handle<Gltf>>,
+    asset_server: &'a AssetServer,
+    gltfs: &'a Assets<Gltf>,
+}
+
+impl AssetFolder {
+    fn is_empty(&self) -> bool {
+        self.folders.is_empty() && self.files.is_empty()
+    }
+    // Files in this folder and every folder below it
+    fn all_files(&self) -> Box<dyn Iterator<Item = &AssetFile> + '_> {
+        Box::new(
+            self.files
+                .iter()
+                .chain(self.folders.iter().flat_map(AssetFolder::all_files)),
+        )
+    }
+}
+
+fn scan_assets(asset_roots: &AssetRoots) -> Vec<AssetFolder> {
+    let default_root = asset_roots.default_root_path();
+    let default_name = default_root.file_name().map_or_else(
+        || "assets".to_owned(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let mut roots = vec![scan_folder(&default_root, default_name, asset_roots)];
+    for root in &asset_roots.extra_roots {
+        roots.push(scan_folder(
+            &asset_roots.root_path(root),
+            root.name.clone(),
+            asset_roots,
+        ));
+    }
+    roots
+}
+fn scan_folder(path: &Path, name: String, asset_roots: &AssetRoots) -> AssetFolder {
+    let mut folder = AssetFolder { name, ..default() };
+    let Ok(entries) = fs::read_dir(path) else {
+        return folder;
+    };
+    for entry in entries.flatten() {
+        let entry_name = entry.file_name().to_string_lossy().to_string();
+        if entry_name.starts_with('.') {
+            continue;
+        }
+        let entry_path = entry.path();
+        // file_type doesn't follow symlinks, so a link to a parent folder can't loop forever
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            let subfolder = scan_folder(&entry_path, entry_name, asset_roots);
+            // Folders without anything to place would only clutter the tree
+            if !subfolder.is_empty() {
+                folder.folders.push(subfolder);
+            }
+            continue;
+        }
+        let listed = entry_path.extension().is_some_and(|extension| {
+            BROWSER_EXTENSIONS
+                .iter()
+                .any(|listed| extension.eq_ignore_ascii_case(listed))
+        });
+        if !listed {
+            continue;
+        }
+        if let Some(asset_path) = asset_roots.asset_path(&entry_path) {
+            folder.files.push(AssetFile {
+                name: entry_name,
+                asset_path,
+            });
+        }
+    }
+    folder.folders.sort_by(|a, b| a.name.cmp(&b.name));
+    folder.files.sort_by(|a, b| a.name.cmp(&b.name));
+    folder
+}
+
+/// Panel listing the assets that can be placed, drag one into the viewport to place it
+pub fn asset_browser_ui(
+    mut egui_ctx: EguiContexts,
+    mut browser: ResMut<AssetBrowser>,
+    mut thumbnails: ResMut<Thumbnails>,
+    asset_server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+    mut cmd_writer: MessageWriter<EditorCommand>,
+) {
+    let ctx = egui_ctx.ctx_mut().unwrap();
+    // Released over the viewport rather than over one of the panels
+    if ctx.input(|input| input.pointer.any_released()) && !ctx.is_pointer_over_area() {
+        if let Some(dragged) = egui::DragAndDrop::take_payload::<DraggedAsset>(ctx) {
+            cmd_writer.write(EditorCommand::SpawnAsset((*dragged).clone()));
+        }
+    }
+    if !browser.open {
+        return;
+    }
+    let browser = &mut *browser;
+    egui::TopBottomPanel::bottom("asset_browser")
+        .resizable(true)
+        .default_height(200.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Assets");
+                ui.add(egui::TextEdit::singleline(&mut browser.search).hint_text("Search..."));
+                if ui.button("Refresh").clicked() {
+                    cmd_writer.write(EditorCommand::RefreshAssetBrowser);
+                }
+                if ui.button("Import...").clicked() {
+                    cmd_writer.write(EditorCommand::ImportAsset);
+                }
+                if ui.button("Close").clicked() {
+                    browser.open = false;
+                }
+            });
+            ui.separator();
+            let mut file_ctx = FileUiContext {
+                thumbnails: &mut thumbnails,
+                contents: &mut browser.contents,
+                asset_server: &asset_server,
+                gltfs: &gltfs,
+            };
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.set_min_width(ui.available_width());
+                // Searching shows the matching files of every folder in one list
+                if !browser.search.is_empty() {
+                    let search = browser.search.to_lowercase();
+                    for root in &browser.roots {
+                        for file in root
+                            .all_files()
+                            .filter(|file| file.asset_path.to_lowercase().contains(&search))
+                        {
+                            asset_file_ui(ui, file, &file.asset_path, &mut file_ctx);
+                        }
+                    }
+                    return;
+                }
+                for root in &browser.roots {
+                    asset_folder_ui(ui, root, &root.name, &mut file_ctx);
+                }
+            });
+        });
+}
+fn asset_folder_ui(
+    ui: &mut egui::Ui,
+    folder: &AssetFolder,
+    id_path: &str,
+    file_ctx: &mut FileUiContext,
+) {
+    egui::CollapsingHeader::new(&folder.name)
+        .id_salt(("asset_folder", id_path))
+        .show(ui, |ui| {
+            for subfolder in &folder.folders {
+                asset_folder_ui(
+                    ui,
+                    subfolder,
+                    &format!("{}/{}", id_path, subfolder.name),
+                    file_ctx,
+                );
+            }
+            for file in &folder.files {
+                asset_file_ui(ui, file, &file.name, file_ctx);
+            }
+        });
+}
+fn asset_file_ui(ui: &mut egui::Ui, file: &AssetFile, label: &str, file_ctx: &mut FileUiContext) {
+    // Only files that are actually shown get their thumbnail rendered
+    let thumbnail = file_ctx.thumbnails.texture(ui.ctx(), &file.asset_path);
+    let stem = file_stem(&file.asset_path);
+    ui.dnd_drag_source(
+        egui::Id::new(("asset_file", &file.asset_path)),
+        DraggedAsset {
+            asset_path: file.asset_path.clone(),
+            label: None,
+            name: stem.clone(),
+        },
+        |ui| {
+            ui.horizontal(|ui| {
+                let size = egui::Vec2::splat(THUMBNAIL_DISPLAY_SIZE);
+                match thumbnail {
+                    Some(texture) => {
+                        ui.add(egui::Image::new((texture, size)));
+                    }
+                    // Keep the rows the same height while thumbnails are still being made
+                    None => {
+                        ui.allocate_space(size);
+                    }
+                }
+                ui.label(label);
+            });
+        },
+    )
+    .response
+    .on_hover_text(&file.asset_path);
+
+    // Drag a single scene, node or mesh out instead of the glTF's first scene
+    egui::CollapsingHeader::new("Contents")
+        .id_salt(("asset_contents", &file.asset_path))
+        .show(ui, |ui| {
+            let asset_server = file_ctx.asset_server;
+            let gltf = file_ctx
+                .contents
+                .entry(file.asset_path.clone())
+                .or_insert_with(|| asset_server.load(file.asset_path.clone()));
+            let Some(gltf) = file_ctx.gltfs.get(&*gltf) else {
+                if asset_server.load_state(&*gltf).is_failed() {
+                    ui.label("Couldn't load the glTF");
+                } else {
+                    ui.spinner();
+                }
+                return;
+            };
+            for entry in gltf_entries(gltf) {
+                let text = match &entry.name {
+                    Some(name) => format!("{} ({})", name, entry.label),
+                    None => entry.label.clone(),
+                };
+                ui.dnd_drag_source(
+                    egui::Id::new(("asset_entry", &file.asset_path, &entry.label)),
+                    DraggedAsset {
+                        asset_path: file.asset_path.clone(),
+                        name: entry.name.unwrap_or_else(|| stem.clone()),
+                        label: Some(entry.label),
+                    },
+                    |ui| {
+                        ui.label(text);
+                    },
+                );
+            }
+        });
+}
+fn file_stem(asset_path: &str) -> String {
+    Path::new(asset_path).file_stem().map_or_else(
+        || asset_path.to_owned(),
+        |stem| stem.to_string_lossy().to_string(),
+    )
+}
+
+pub fn toggle_asset_browser_system(
+    mut browser: ResMut<AssetBrowser>,
+    asset_roots: Res<EditorAssetRoots>,
+) {
+    browser.open = !browser.open;
+    // Files may have been added since it was last open
+    if browser.open {
+        browser.roots = scan_assets(&asset_roots);
+    }
+}
+pub fn refresh_asset_browser_system(
+    mut browser: ResMut<AssetBrowser>,
+    mut thumbnails: ResMut<Thumbnails>,
+    asset_roots: Res<EditorAssetRoots>,
+) {
+    browser.roots = scan_assets(&asset_roots);
+    // Files that changed get new thumbnails, unchanged ones come back from the disk cache
+    thumbnails.clear();
+}
+/// Pick a glTF anywhere on disk, copying it into the asset folders if it's outside them,
+/// and show it in the browser
+pub fn import_asset_system(world: &mut World) {
+    let asset_roots = world.resource::<EditorAssetRoots>();
+    let Some(path) = FileDialog::new()
+        .add_filter("glTF", &BROWSER_EXTENSIONS)
+        .set_directory(asset_roots.default_root_path())
+        .pick_file()
+    else {
+        return;
+    };
+    let Some(asset_path) = asset_path_or_import(&path, asset_roots) else {
+        return;
+    };
+    let roots = scan_assets(asset_roots);
+    let mut browser = world.resource_mut::<AssetBrowser>();
+    browser.roots = roots;
+    browser.search = asset_path;
+    browser.open = true;
+}
+/// Place a glTF from the asset browser at the point under the cursor
+pub fn spawn_asset_at_cursor(In(asset): In<DraggedAsset>, world: &mut World) {
+    let translation = world
+        .run_system_cached(cursor_hit_point)
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    // finish_loading_scene spawns the glTF scene under it
+    let entity = world
+        .spawn((
+            Name::new(asset.name),
+            GltfRef {
+                asset_path: asset.asset_path,
+                label: asset.label,
+            },
+            Transform::from_translation(translation),
+            Visibility::Visible,
+            IncludeInSave,
+        ))
+        .id();
+    world
+        .resource_mut::<EditorHistory>()
+        .push(EditorEdit::Spawn {
+            root: entity,
+            snapshot: Vec::new(),
+        });
+    world.resource_mut::<EditorSelected>().select_only(entity);
+}
@@ -0,0 +1,532 @@
+use std::any::TypeId;
+
+use bevy::{
+    asset::{ReflectAsset, ReflectHandle, UntypedAssetId},
+    prelude::*,
+    reflect::{
+        DynamicEnum, DynamicStruct, DynamicTuple, DynamicVariant, ReflectMut, ReflectRef, Struct,
+        TypeInfo, TypeRegistry, VariantInfo,
+    },
+};
+use bevy_egui::{EguiContext, PrimaryEguiContext};
+use egui::{Ui, Widget};
+
+use crate::{
+    defs::{EditorSelected, UiBuffers},
+    editor_history::{EditorEdit, EditorHistory},
+    saving_loading::gameplay_component_types,
+    ui::dropdown_box::DropDownBox,
+};
+
+// Value of a component when the current inspector drag/edit of it started
+pub struct ComponentEditStart {
+    entity: Entity,
+    type_id: TypeId,
+    before: Box<dyn PartialReflect>,
+}
+
+// Components the hierarchy panel already edits, or that bevy computes from other components
+fn hidden_components() -> [TypeId; 7] {
+    [
+        TypeId::of::<Transform>(),
+        TypeId::of::<GlobalTransform>(),
+        TypeId::of::<Name>(),
+        TypeId::of::<ChildOf>(),
+        TypeId::of::<Children>(),
+        TypeId::of::<InheritedVisibility>(),
+        TypeId::of::<ViewVisibility>(),
+    ]
+}
+
+/// Lists every reflected component of the active object with a widget per field. Gameplay
+/// components can be edited, added and removed, the ones the editor manages are read-only
+pub fn component_inspector_ui(
+    world: &mut World,
+    mut edit_start: Local<Option<ComponentEditStart>>,
+) {
+    let Ok(mut egui_ctx) = world
+        .query_filtered::<&mut EguiContext, With<PrimaryEguiContext>>()
+        .single_mut(world)
+    else {
+        return;
+    };
+    let ctx = egui_ctx.get_mut().clone();
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = type_registry.read();
+    let selected = world
+        .resource::<EditorSelected>()
+        .active()
+        .filter(|entity| world.get_entity(*entity).is_ok());
+    let mut search_buf =
+        std::mem::take(&mut world.resource_mut::<UiBuffers>().component_search_buf);
+    let mut changed_this_frame = false;
+    egui::SidePanel::left("component_panel").show(&ctx, |ui| {
+        ui.heading("Components");
+        ui.separator();
+        let Some(entity) = selected else {
+            ui.label("Nothing selected");
+            return;
+        };
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let components = reflected_components(world, &registry, entity);
+            // Editor components like IncludeInSave and GltfRef are kept in sync with the rest
+            // of the editor, removing or typing into them would leave it in a broken state
+            let gameplay_types = gameplay_component_types();
+            for &(type_id, mutable) in &components {
+                let editable = mutable && gameplay_types.contains(&type_id);
+                let Some(reflect_component) = registry.get_type_data::<ReflectComponent>(type_id)
+                else {
+                    continue;
+                };
+                let Some(component) = reflect_component.reflect(world.entity(entity)) else {
+                    continue;
+                };
+                let before = clone_reflect(component);
+                let mut value = clone_reflect(component);
+                let mut removed = false;
+                let changed = egui::CollapsingHeader::new(short_type_path(&registry, type_id))
+                    .id_salt(("component", type_id))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        removed = editable && ui.small_button("Remove").clicked();
+                        ui.add_enabled_ui(editable, |ui| {
+                            field_ui(ui, value.as_mut(), world, &registry)
+                        })
+                        .inner
+                    })
+                    .body_returned
+                    .unwrap_or(false);
+                if removed {
+                    if let Some(edit_start) = edit_start.take() {
+                        finish_component_edit(world, &registry, edit_start);
+                    }
+                    reflect_component.remove(&mut world.entity_mut(entity));
+                    world
+                        .resource_mut::<EditorHistory>()
+                        .push(EditorEdit::Component {
+                            entity,
+                            type_id,
+                            before: Some(before),
+                            after: None,
+                        });
+                } else if changed && editable {
+                    changed_this_frame = true;
+                    if edit_start.as_ref().is_some_and(|edit_start| {
+                        edit_start.entity != entity || edit_start.type_id != type_id
+                    }) {
+                        finish_component_edit(world, &registry, edit_start.take().unwrap());
+                    }
+                    if edit_start.is_none() {
+                        *edit_start = Some(ComponentEditStart {
+                            entity,
+                            type_id,
+                            before,
+                        });
+                    }
+                    reflect_component.apply(world.entity_mut(entity), value.as_ref());
+                }
+            }
+            ui.separator();
+            // Only types with a default value can be added, there's nothing to fill them with.
+            // Anything that isn't saved would be gone after saving, undoing a delete or pasting
+            let mut addable: Vec<(&str, TypeId)> = registry
+                .iter()
+                .filter(|registration| {
+                    registration.data::<ReflectComponent>().is_some()
+                        && registration.data::<ReflectDefault>().is_some()
+                        && gameplay_types.contains(&registration.type_id())
+                        && !components
+                            .iter()
+                            .any(|(type_id, _)| *type_id == registration.type_id())
+                })
+                .map(|registration| {
+                    (
+                        registration.type_info().type_path_table().short_path(),
+                        registration.type_id(),
+                    )
+                })
+                .collect();
+            addable.sort();
+            DropDownBox::from_iter(
+                addable.iter().map(|(name, _)| *name),
+                "add_component_selector",
+                &mut search_buf,
+                |ui, text| ui.selectable_label(false, text),
+            )
+            .hint_text("Find component...")
+            .ui(ui);
+            if ui.button("Add Component").clicked() {
+                if let Some((_, type_id)) = addable.iter().find(|(name, _)| *name == search_buf) {
+                    let reflect_component = registry
+                        .get_type_data::<ReflectComponent>(*type_id)
+                        .unwrap();
+                    let value = registry
+                        .get_type_data::<ReflectDefault>(*type_id)
+                        .unwrap()
+                        .default()
+                        .into_partial_reflect();
+                    reflect_component.insert(
+                        &mut world.entity_mut(entity),
+                        value.as_ref(),
+                        &registry,
+                    );
+                    world
+                        .resource_mut::<EditorHistory>()
+                        .push(EditorEdit::Component {
+                            entity,
+                            type_id: *type_id,
+                            before: None,
+                            after: Some(value),
+                        });
+                    search_buf.clear();
+                }
+            }
+        });
+    });
+    world.resource_mut::<UiBuffers>().component_search_buf = search_buf;
+    // Push one history entry per drag or typed edit rather than one per frame
+    if !changed_this_frame && !ctx.is_using_pointer() && !ctx.wants_keyboard_input() {
+        if let Some(edit_start) = edit_start.take() {
+            finish_component_edit(world, &registry, edit_start);
+        }
+    }
+}
+// Reflected components of the entity in the order they were added, and whether they're mutable
+fn reflected_components(
+    world: &World,
+    registry: &TypeRegistry,
+    entity: Entity,
+) -> Vec<(TypeId, bool)> {
+    world
+        .entity(entity)
+        .archetype()
+        .components()
+        .iter()
+        .filter_map(|component_id| world.components().get_info(*component_id))
+        .filter_map(|info| Some((info.type_id()?, info.mutable())))
+        .filter(|(type_id, _)| {
+            registry
+                .get_type_data::<ReflectComponent>(*type_id)
+                .is_some()
+                && !hidden_components().contains(type_id)
+        })
+        .collect()
+}
+fn finish_component_edit(
+    world: &mut World,
+    registry: &TypeRegistry,
+    edit_start: ComponentEditStart,
+) {
+    let Some(after) = registry
+        .get_type_data::<ReflectComponent>(edit_start.type_id)
+        .zip(world.get_entity(edit_start.entity).ok())
+        .and_then(|(reflect_component, entity_ref)| reflect_component.reflect(entity_ref))
+        .map(clone_reflect)
+    else {
+        return;
+    };
+    if edit_start.before.reflect_partial_eq(after.as_ref()) != Some(true) {
+        world
+            .resource_mut::<EditorHistory>()
+            .push(EditorEdit::Component {
+                entity: edit_start.entity,
+                type_id: edit_start.type_id,
+                before: Some(edit_start.before),
+                after: Some(after),
+            });
+    }
+}
+// A concrete copy when the type supports it, so colors and handles can still be downcast
+fn clone_reflect(value: &dyn Reflect) -> Box<dyn PartialReflect> {
+    value
+        .reflect_clone()
+        .map(Reflect::into_partial_reflect)
+        .unwrap_or_else(|_| value.to_dynamic())
+}
+fn short_type_path(registry: &TypeRegistry, type_id: TypeId) -> String {
+    registry.get(type_id).map_or_else(
+        || format!("{:?}", type_id),
+        |registration| {
+            registration
+                .type_info()
+                .type_path_table()
+                .short_path()
+                .to_owned()
+        },
+    )
+}
+// Widget for a reflected value, returns whether it was changed
+fn field_ui(
+    ui: &mut Ui,
+    value: &mut dyn PartialReflect,
+    world: &World,
+    registry: &TypeRegistry,
+) -> bool {
+    if let Some(reflect_handle) = handle_type_data(value, registry) {
+        return handle_ui(ui, value, reflect_handle, world, registry);
+    }
+    if let Some(color) = value.try_downcast_mut::<Color>() {
+        let Srgba {
+            red,
+            green,
+            blue,
+            alpha,
+        } = color.to_srgba();
+        let mut rgba = [red, green, blue, alpha];
+        let changed = ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed();
+        if changed {
+            *color = Color::srgba(rgba[0], rgba[1], rgba[2], rgba[3]);
+        }
+        return changed;
+    }
+    if let Some(rotation) = value.try_downcast_mut::<Quat>() {
+        let (pitch, yaw, roll) = rotation.to_euler(EulerRot::XYZ);
+        let (mut pitch, mut yaw, mut roll) =
+            (pitch.to_degrees(), yaw.to_degrees(), roll.to_degrees());
+        let changed = ui.add(egui::DragValue::new(&mut pitch).prefix("Pitch: "))
+            | ui.add(egui::DragValue::new(&mut yaw).prefix("Yaw: "))
+            | ui.add(egui::DragValue::new(&mut roll).prefix("Roll: "));
+        if changed.changed() {
+            *rotation = Quat::from_euler(
+                EulerRot::XYZ,
+                pitch.to_radians(),
+                yaw.to_radians(),
+                roll.to_radians(),
+            );
+        }
+        return changed.changed();
+    }
+    let mut changed = false;
+    match value.reflect_mut() {
+        ReflectMut::Struct(value) if is_vector(value) => {
+            for index in 0..value.field_len() {
+                let name = value.name_at(index).unwrap_or_default().to_owned();
+                if let Some(number) = value
+                    .field_at_mut(index)
+                    .and_then(|field| field.try_downcast_mut::<f32>())
+                {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(number)
+                                .prefix(format!("{}: ", name))
+                                .speed(0.1),
+                        )
+                        .changed();
+                }
+            }
+        }
+        ReflectMut::Struct(value) => {
+            for index in 0..value.field_len() {
+                let name = value.name_at(index).unwrap_or_default().to_owned();
+                if let Some(field) = value.field_at_mut(index) {
+                    changed |= labeled_field_ui(ui, &name, field, world, registry);
+                }
+            }
+        }
+        ReflectMut::TupleStruct(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_mut(index) {
+                    changed |= labeled_field_ui(ui, &index.to_string(), field, world, registry);
+                }
+            }
+        }
+        ReflectMut::Tuple(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_mut(index) {
+                    changed |= labeled_field_ui(ui, &index.to_string(), field, world, registry);
+                }
+            }
+        }
+        ReflectMut::List(value) => {
+            for index in 0..value.len() {
+                if let Some(item) = value.get_mut(index) {
+                    changed |= labeled_field_ui(ui, &index.to_string(), item, world, registry);
+                }
+            }
+        }
+        ReflectMut::Array(value) => {
+            for index in 0..value.len() {
+                if let Some(item) = value.get_mut(index) {
+                    changed |= labeled_field_ui(ui, &index.to_string(), item, world, registry);
+                }
+            }
+        }
+        ReflectMut::Enum(value) => {
+            if let Some(TypeInfo::Enum(info)) = value.get_represented_type_info() {
+                let current = value.variant_name().to_owned();
+                let mut new_variant = None;
+                egui::ComboBox::from_id_salt(ui.next_auto_id())
+                    .selected_text(&current)
+                    .show_ui(ui, |ui| {
+                        for variant in info.iter() {
+                            // Variants whose fields have no default value can't be switched to
+                            let Some(default) = default_variant(variant, registry) else {
+                                continue;
+                            };
+                            if ui
+                                .selectable_label(variant.name() == current, variant.name())
+                                .clicked()
+                                && variant.name() != current
+                            {
+                                new_variant = Some(default);
+                            }
+                        }
+                    });
+                if let Some(new_variant) = new_variant {
+                    changed |= value.try_apply(&new_variant).is_ok();
+                }
+            }
+            for index in 0..value.field_len() {
+                let name = value
+                    .name_at(index)
+                    .map_or_else(|| index.to_string(), str::to_owned);
+                if let Some(field) = value.field_at_mut(index) {
+                    changed |= labeled_field_ui(ui, &name, field, world, registry);
+                }
+            }
+        }
+        ReflectMut::Opaque(value) => changed = opaque_ui(ui, value),
+        // Maps and sets are shown but not editable
+        _ => {
+            ui.label(format!("{:?}", value));
+        }
+    }
+    changed
+}
+// Simple values go on one line next to their name, the others get their fields indented below
+fn labeled_field_ui(
+    ui: &mut Ui,
+    name: &str,
+    value: &mut dyn PartialReflect,
+    world: &World,
+    registry: &TypeRegistry,
+) -> bool {
+    let inline = handle_type_data(value, registry).is_some()
+        || value.try_downcast_ref::<Color>().is_some()
+        || match value.reflect_ref() {
+            ReflectRef::Opaque(_) => true,
+            ReflectRef::Struct(value) => is_vector(value),
+            ReflectRef::Enum(value) => value.field_len() == 0,
+            _ => false,
+        };
+    if inline {
+        ui.horizontal(|ui| {
+            ui.label(name);
+            field_ui(ui, value, world, registry)
+        })
+        .inner
+    } else {
+        ui.label(name);
+        ui.indent(name, |ui| field_ui(ui, value, world, registry))
+            .inner
+    }
+}
+// Vec2/Vec3/Vec4 and the like, drawn as one row of numbers
+fn is_vector(value: &dyn Struct) -> bool {
+    value.field_len() > 0
+        && (0..value.field_len()).all(|index| {
+            value
+                .field_at(index)
+                .is_some_and(|field| field.try_downcast_ref::<f32>().is_some())
+        })
+}
+fn opaque_ui(ui: &mut Ui, value: &mut dyn PartialReflect) -> bool {
+    macro_rules! number_ui {
+        ($($number:ty),*) => {
+            $(
+                if let Some(number) = value.try_downcast_mut::<$number>() {
+                    return ui.add(egui::DragValue::new(number).speed(0.1)).changed();
+                }
+            )*
+        };
+    }
+    number_ui!(f32, f64, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+    if let Some(flag) = value.try_downcast_mut::<bool>() {
+        return ui.checkbox(flag, "").changed();
+    }
+    if let Some(text) = value.try_downcast_mut::<String>() {
+        return ui.text_edit_singleline(text).changed();
+    }
+    ui.label(format!("{:?}", value));
+    false
+}
+fn handle_type_data<'a>(
+    value: &dyn PartialReflect,
+    registry: &'a TypeRegistry,
+) -> Option<&'a ReflectHandle> {
+    let type_id = value.get_represented_type_info()?.type_id();
+    registry.get_type_data::<ReflectHandle>(type_id)
+}
+// Pick one of the loaded assets of the handle's type by its path
+fn handle_ui(
+    ui: &mut Ui,
+    value: &mut dyn PartialReflect,
+    reflect_handle: &ReflectHandle,
+    world: &World,
+    registry: &TypeRegistry,
+) -> bool {
+    let asset_server = world.resource::<AssetServer>();
+    let current = value
+        .try_as_reflect()
+        .and_then(|handle| reflect_handle.downcast_handle_untyped(handle.as_any()));
+    let current_text = current.as_ref().map_or_else(
+        || "None".to_owned(),
+        |handle| {
+            asset_server
+                .get_path(handle.id())
+                .map_or_else(|| "unnamed asset".to_owned(), |path| path.to_string())
+        },
+    );
+    // Assets that weren't loaded from a file have no path to pick them by
+    let Some(reflect_asset) =
+        registry.get_type_data::<ReflectAsset>(reflect_handle.asset_type_id())
+    else {
+        ui.label(current_text);
+        return false;
+    };
+    let mut picked = None;
+    egui::ComboBox::from_id_salt(ui.next_auto_id())
+        .selected_text(current_text)
+        .show_ui(ui, |ui| {
+            let mut assets: Vec<(String, UntypedAssetId)> = reflect_asset
+                .ids(world)
+                .filter_map(|id| Some((asset_server.get_path(id)?.to_string(), id)))
+                .collect();
+            assets.sort_by(|(path, _), (other_path, _)| path.cmp(other_path));
+            for (path, id) in assets {
+                let is_current = current.as_ref().is_some_and(|handle| handle.id() == id);
+                if ui.selectable_label(is_current, &path).clicked() && !is_current {
+                    picked = asset_server.get_id_handle_untyped(id);
+                }
+            }
+        });
+    let Some(picked) = picked else {
+        return false;
+    };
+    value
+        .try_apply(reflect_handle.typed(picked).as_partial_reflect())
+        .is_ok()
+}
+// The variant with default field values, None when a field's type has no default
+fn default_variant(variant: &VariantInfo, registry: &TypeRegistry) -> Option<DynamicEnum> {
+    let dynamic_variant = match variant {
+        VariantInfo::Unit(_) => DynamicVariant::Unit,
+        VariantInfo::Tuple(info) => {
+            let mut fields = DynamicTuple::default();
+            for field in info.iter() {
+                let default = registry.get_type_data::<ReflectDefault>(field.type_id())?;
+                fields.insert_boxed(default.default().into_partial_reflect());
+            }
+            DynamicVariant::Tuple(fields)
+        }
+        VariantInfo::Struct(info) => {
+            let mut fields = DynamicStruct::default();
+            for field in info.iter() {
+                let default = registry.get_type_data::<ReflectDefault>(field.type_id())?;
+                fields.insert_boxed(field.name(), default.default().into_partial_reflect());
+            }
+            DynamicVariant::Struct(fields)
+        }
+    };
+    Some(DynamicEnum::new(variant.name(), dynamic_variant))
+}
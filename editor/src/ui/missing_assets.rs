@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::{
+    defs::GltfRef,
+    execute_editor_commands::EditorCommand,
+    missing_assets::{MissingAsset, MissingAssetsPanel},
+};
+
+/// Window listing the glTFs that couldn't be loaded, each can be relinked to a file on disk or
+/// a whole folder of them swapped for another
+pub fn missing_assets_ui(
+    mut egui_ctx: EguiContexts,
+    mut panel: ResMut<MissingAssetsPanel>,
+    missing_q: Query<&GltfRef, With<MissingAsset>>,
+    mut cmd_writer: MessageWriter<EditorCommand>,
+) {
+    if !panel.open {
+        return;
+    }
+    // Objects missing the same file are relinked together
+    let mut missing: BTreeMap<&str, usize> = BTreeMap::new();
+    for gltf_ref in &missing_q {
+        *missing.entry(gltf_ref.asset_path.as_str()).or_default() += 1;
+    }
+    let panel = &mut *panel;
+    let mut open = panel.open;
+    egui::Window::new("Missing Assets")
+        .open(&mut open)
+        .default_width(360.0)
+        .show(egui_ctx.ctx_mut().unwrap(), |ui| {
+            if missing.is_empty() {
+                ui.label("No missing assets");
+                return;
+            }
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    for (asset_path, count) in &missing {
+                        ui.horizontal(|ui| {
+                            if ui.button("Relink...").clicked() {
+                                cmd_writer
+                                    .write(EditorCommand::RelinkAsset((*asset_path).to_owned()));
+                            }
+                            ui.label(format!("{} ({} object(s))", asset_path, count));
+                        });
+                    }
+                });
+            ui.separator();
+            ui.label("Replace folder");
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut panel.from_folder)
+                        .hint_text("models/old/")
+                        .desired_width(120.0),
+                );
+                ui.label("with");
+                ui.add(
+                    egui::TextEdit::singleline(&mut panel.to_folder)
+                        .hint_text("models/new/")
+                        .desired_width(120.0),
+                );
+                if ui
+                    .add_enabled(!panel.from_folder.is_empty(), egui::Button::new("Apply"))
+                    .clicked()
+                {
+                    cmd_writer.write(EditorCommand::SubstituteAssetFolder {
+                        from: panel.from_folder.clone(),
+                        to: panel.to_folder.clone(),
+                    });
+                }
+            });
+        });
+    panel.open = open;
+}
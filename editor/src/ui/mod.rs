@@ -1,2 +1,5 @@
+pub mod asset_browser;
+pub mod component_inspector;
 mod dropdown_box;
+pub mod missing_assets;
 pub mod ui_plugin;
@@ -1,51 +1,169 @@
-use bevy::{
-    color::palettes::css::{BLUE, GREEN, RED},
-    prelude::*,
-};
+use bevy::prelude::*;
 use bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass};
 use egui::{Frame, Id, Ui, Widget};
 
 use crate::{
     defs::{
-        CurrentObjectManipulationMode, EditorChildOf, EditorSelected, IncludeInSave,
-        ManipulationMode, RotationEditState, UiBuffers,
+        AutosaveSettings, CurrentObjectManipulationMode, EditorChildOf, EditorSelected,
+        EditorStatus, GizmoTool, IncludeInSave, ManipulationMode, MoveState, PivotPoint,
+        PrefabLink, PrefabRoot, RotationEditState, SnapSettings, SnapTarget, SubLevelRoot,
+        TransformGizmoState, UiBuffers,
+    },
+    editor_history::{EditorEdit, EditorHistory, HierarchyState},
+    execute_editor_commands::EditorCommand,
+    sub_levels::DEFAULT_LOAD_DISTANCE,
+    ui::{
+        asset_browser::{AssetBrowser, asset_browser_ui},
+        component_inspector::component_inspector_ui,
+        dropdown_box::DropDownBox,
+        missing_assets::missing_assets_ui,
     },
-    ui::dropdown_box::DropDownBox,
 };
+// Values of the selected entity when the current inspector drag/edit started
+#[derive(Default)]
+struct InspectorEditStart {
+    transform: Option<Transform>,
+    load_distance: Option<Option<f32>>,
+}
 pub struct EditorUiPlugin;
 impl Plugin for EditorUiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(EguiPrimaryContextPass, hierarchy_ui)
-            .add_plugins(EguiPlugin::default())
-            .add_systems(Update, draw_selection_gizmos)
-            .insert_resource(UiBuffers::default());
+        // The menu and status bars have to claim their space before the side panel
+        app.add_systems(
+            EguiPrimaryContextPass,
+            (
+                menu_bar,
+                status_bar,
+                asset_browser_ui,
+                hierarchy_ui,
+                component_inspector_ui,
+                missing_assets_ui,
+            )
+                .chain(),
+        )
+        .add_systems(EguiPrimaryContextPass, manipulation_overlay)
+        .add_plugins(EguiPlugin::default())
+        .insert_resource(UiBuffers::default())
+        .insert_resource(AssetBrowser::default());
     }
 }
-fn draw_selection_gizmos(
-    mut gizmos: Gizmos,
-    selected_entity_res: Res<EditorSelected>,
-    global_transforms: Query<&GlobalTransform>,
+fn menu_bar(
+    mut egui_ctx: EguiContexts,
+    mut cmd_writer: MessageWriter<EditorCommand>,
+    mut autosave: ResMut<AutosaveSettings>,
+) {
+    egui::TopBottomPanel::top("menu_bar").show(egui_ctx.ctx_mut().unwrap(), |ui| {
+        egui::MenuBar::new().ui(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                if ui.button("New Level (Ctrl+N)").clicked() {
+                    cmd_writer.write(EditorCommand::NewLevel);
+                }
+                if ui.button("Open Level... (Ctrl+O)").clicked() {
+                    cmd_writer.write(EditorCommand::OpenLevel);
+                }
+                if ui.button("Save (Ctrl+S)").clicked() {
+                    cmd_writer.write(EditorCommand::Save);
+                }
+                if ui.button("Save As... (Ctrl+Shift+S)").clicked() {
+                    cmd_writer.write(EditorCommand::SaveAs);
+                }
+                ui.separator();
+                if ui.button("Asset Browser (O)").clicked() {
+                    cmd_writer.write(EditorCommand::ToggleAssetBrowser);
+                }
+                if ui.button("Missing Assets").clicked() {
+                    cmd_writer.write(EditorCommand::ToggleMissingAssets);
+                }
+                if ui.button("Add Prefab...").clicked() {
+                    cmd_writer.write(EditorCommand::AddPrefab);
+                }
+                if ui.button("Create Prefab from Active Object...").clicked() {
+                    cmd_writer.write(EditorCommand::CreatePrefab);
+                }
+                if ui.button("Add Sub-level...").clicked() {
+                    cmd_writer.write(EditorCommand::AddSubLevel);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut autosave.enabled, "Autosave every");
+                    let mut minutes = autosave.interval_seconds / 60.0;
+                    let changed = ui
+                        .add_enabled(
+                            autosave.enabled,
+                            egui::DragValue::new(&mut minutes)
+                                .range(0.5..=60.0)
+                                .speed(0.1)
+                                .suffix(" min"),
+                        )
+                        .changed();
+                    if changed {
+                        autosave.interval_seconds = minutes * 60.0;
+                    }
+                });
+            });
+        });
+    });
+}
+// Result of the last save/load, errors stay in red until dismissed or replaced
+fn status_bar(mut egui_ctx: EguiContexts, mut status: ResMut<EditorStatus>) {
+    egui::TopBottomPanel::bottom("status_bar").show(egui_ctx.ctx_mut().unwrap(), |ui| {
+        ui.horizontal(|ui| {
+            let Some(message) = &status.message else {
+                ui.label("");
+                return;
+            };
+            if message.is_error {
+                ui.colored_label(ui.visuals().error_fg_color, &message.text);
+                if ui.small_button("Dismiss").clicked() {
+                    status.message = None;
+                }
+            } else {
+                ui.label(&message.text);
+            }
+        });
+    });
+}
+// Small overlay showing the current manipulation and any value typed for it
+fn manipulation_overlay(
+    mut egui_ctx: EguiContexts,
     manip_mode: Res<CurrentObjectManipulationMode>,
+    move_state: Res<MoveState>,
 ) {
-    if let Some(selected_entity) = selected_entity_res.0 {
-        let Ok(selected_entity_transform) = global_transforms.get(selected_entity) else {
-            return;
-        };
-        let pos = selected_entity_transform.translation();
-        let length = 300.0;
-
-        // Determine axes directions
-        let (x_axis, y_axis, z_axis) = if manip_mode.local {
-            let rot = selected_entity_transform.rotation();
-            (rot * Vec3::X, rot * Vec3::Y, rot * Vec3::Z)
-        } else {
-            (Vec3::X, Vec3::Y, Vec3::Z)
+    let mode_name = match manip_mode.mode {
+        ManipulationMode::None => return,
+        ManipulationMode::Move => "Move",
+        ManipulationMode::Rotate => "Rotate",
+        ManipulationMode::Scale => "Scale",
+    };
+    let axis_name = match (manip_mode.axis_restriction, manip_mode.plane_restriction) {
+        (Some(axis), _) => format!(" along {:?}", axis),
+        (None, Some(axis)) => format!(" excluding {:?}", axis),
+        (None, None) => String::new(),
+    };
+    let space_name = if manip_mode.local { "local" } else { "global" };
+    let value_text = if !manip_mode.takes_typed_value() {
+        "typed values need an axis (X, Y or Z), Enter to confirm, Esc to cancel".to_owned()
+    } else if move_state.typed_input.is_empty() {
+        "type a value, Enter to confirm, Esc to cancel".to_owned()
+    } else {
+        let unit = match manip_mode.mode {
+            ManipulationMode::Rotate => "°",
+            ManipulationMode::Scale => "x",
+            _ => "",
         };
-
-        gizmos.line(pos - x_axis * length, pos + x_axis * length, BLUE);
-        gizmos.line(pos - y_axis * length, pos + y_axis * length, RED);
-        gizmos.line(pos - z_axis * length, pos + z_axis * length, GREEN);
-    }
+        format!("{}{}", move_state.typed_input, unit)
+    };
+    egui::Area::new(Id::new("manipulation_overlay"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 12.0))
+        .interactable(false)
+        .show(egui_ctx.ctx_mut().unwrap(), |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!(
+                    "{}{} ({}): {}",
+                    mode_name, axis_name, space_name, value_text
+                ));
+            });
+        });
 }
 fn hierarchy_ui(
     mut egui_ctx: EguiContexts,
@@ -65,6 +183,13 @@ fn hierarchy_ui(
     mut all_transforms: Query<&mut Transform>,
     mut all_global_transforms: Query<&mut GlobalTransform>,
     mut editing_name: Local<Option<Entity>>,
+    mut history: ResMut<EditorHistory>,
+    mut manip_mode: ResMut<CurrentObjectManipulationMode>,
+    mut snap_settings: ResMut<SnapSettings>,
+    mut gizmo_state: ResMut<TransformGizmoState>,
+    mut inspector_edit_start: Local<InspectorEditStart>,
+    prefab_q: Query<(Option<&PrefabRoot>, Has<PrefabLink>)>,
+    mut sub_level_q: Query<&mut SubLevelRoot>,
 ) {
     egui::SidePanel::right("hierarchy_panel").show(egui_ctx.ctx_mut().unwrap(), |ui| {
         ui.heading("Hierarchy");
@@ -95,17 +220,37 @@ fn hierarchy_ui(
                         &mut commands,
                         &mut editing_name,
                         &mut ui_buffers,
+                        &mut history,
                         &input,
+                        &sub_level_q,
                     );
                 }
             },
         );
         // If something was dropped, but NOT onto a node:
         if let Some(dragged_entity) = root_drop {
+            let old_parent = query
+                .get(*dragged_entity)
+                .ok()
+                .and_then(|(_, _, _, eco)| eco.map(|eco| eco.0));
             // set the transform to the computed GlobalTransform, so it stays in the same spot
             if let Ok(selected_global_transform) = all_global_transforms.get(*dragged_entity) {
                 if let Ok(mut selected_transform) = all_transforms.get_mut(*dragged_entity) {
+                    let old_transform = *selected_transform;
                     *selected_transform = selected_global_transform.compute_transform();
+                    if old_parent.is_some() {
+                        history.push(EditorEdit::Reparent {
+                            entity: *dragged_entity,
+                            before: HierarchyState {
+                                parent: old_parent,
+                                transform: old_transform,
+                            },
+                            after: HierarchyState {
+                                parent: None,
+                                transform: *selected_transform,
+                            },
+                        });
+                    }
                 }
             }
             // Reset its hierarchy to be a Root
@@ -113,7 +258,70 @@ fn hierarchy_ui(
             commands.entity(*dragged_entity).remove::<ChildOf>();
         }
         ui.separator();
-        if let Some(selected) = selected_entity.0 {
+        ui.horizontal(|ui| {
+            ui.label("Gizmo:");
+            ui.radio_value(&mut gizmo_state.tool, GizmoTool::Translate, "Move");
+            ui.radio_value(&mut gizmo_state.tool, GizmoTool::Rotate, "Rotate");
+            ui.radio_value(&mut gizmo_state.tool, GizmoTool::Scale, "Scale");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Pivot:");
+            ui.radio_value(
+                &mut manip_mode.pivot,
+                PivotPoint::MedianPoint,
+                "Median Point",
+            );
+            ui.radio_value(
+                &mut manip_mode.pivot,
+                PivotPoint::ActiveObject,
+                "Active Object",
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut snap_settings.enabled, "Snap (Shift+Tab, hold Ctrl)");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Move to:");
+            ui.radio_value(
+                &mut snap_settings.target,
+                SnapTarget::Increment,
+                "Increment",
+            );
+            ui.radio_value(&mut snap_settings.target, SnapTarget::Surface, "Surface");
+            ui.radio_value(&mut snap_settings.target, SnapTarget::Vertex, "Vertex");
+        });
+        if snap_settings.target != SnapTarget::Increment {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut snap_settings.snap_bounds_bottom, "Snap bounds bottom");
+                ui.checkbox(&mut snap_settings.align_to_normal, "Align to normal");
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut snap_settings.translation_step)
+                    .prefix("Grid: ")
+                    .speed(0.05)
+                    .range(0.01..=100.0),
+            );
+            ui.add(
+                egui::DragValue::new(&mut snap_settings.rotation_step_degrees)
+                    .prefix("Angle: ")
+                    .suffix("°")
+                    .speed(1.0)
+                    .range(1.0..=180.0),
+            );
+            ui.add(
+                egui::DragValue::new(&mut snap_settings.scale_step)
+                    .prefix("Scale: ")
+                    .speed(0.01)
+                    .range(0.01..=10.0),
+            );
+        });
+        ui.separator();
+        if let Some(selected) = selected_entity.active() {
+            if selected_entity.0.len() > 1 {
+                ui.label(format!("{} objects selected", selected_entity.0.len()));
+            }
             ui.label(format!(
                 "Selected: {:?}",
                 query
@@ -121,6 +329,70 @@ fn hierarchy_ui(
                     .ok()
                     .map(|(_, n, _, _)| n.map_or("no name", |n| n.as_str()))
             ));
+            // Walk up to the root of the prefab instance the selection is part of
+            let mut prefab_root = None;
+            let mut current = Some(selected);
+            while let Some(entity) = current {
+                match prefab_q.get(entity) {
+                    Ok((Some(root), _)) => {
+                        prefab_root = Some(root);
+                        break;
+                    }
+                    Ok((None, true)) => {
+                        current = query
+                            .get(entity)
+                            .ok()
+                            .and_then(|(_, _, _, eco)| eco.map(|eco| eco.0));
+                    }
+                    _ => break,
+                }
+            }
+            if let Ok(mut sub_level) = sub_level_q.get_mut(selected) {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Sub-level: {}", sub_level.path));
+                    if ui.button("Open Individually").clicked() {
+                        commands.write_message(EditorCommand::OpenSubLevel);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let load_distance_before_edit = sub_level.load_distance;
+                    let mut streamed = load_distance_before_edit.is_some();
+                    let mut response = ui.checkbox(&mut streamed, "Stream in game within");
+                    if response.changed() {
+                        sub_level.load_distance = streamed.then_some(DEFAULT_LOAD_DISTANCE);
+                    }
+                    if let Some(load_distance) = &mut sub_level.load_distance {
+                        response |= ui.add(
+                            egui::DragValue::new(load_distance)
+                                .range(1.0..=10000.0)
+                                .suffix(" m"),
+                        );
+                    }
+                    record_inspector_edit(
+                        &mut history,
+                        &mut inspector_edit_start.load_distance,
+                        load_distance_before_edit,
+                        sub_level.load_distance,
+                        response,
+                        |before, after| EditorEdit::LoadDistance {
+                            entity: selected,
+                            before,
+                            after,
+                        },
+                    );
+                });
+            }
+            if let Some(prefab_root) = prefab_root {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Prefab: {}", prefab_root.path));
+                    if ui.button("Apply Overrides").clicked() {
+                        commands.write_message(EditorCommand::ApplyPrefabOverrides);
+                    }
+                    if ui.button("Revert").clicked() {
+                        commands.write_message(EditorCommand::RevertToPrefab);
+                    }
+                });
+            }
             if let Ok(global_transform) = all_global_transforms.get(selected) {
                 if let Ok(mut transform) = all_transforms.get_mut(selected) {
                     ui.separator();
@@ -138,46 +410,43 @@ fn hierarchy_ui(
                     }
                     let (mut delta_pitch, mut delta_yaw, mut delta_roll) =
                         rotation_edit_state.rotation_edit_euler.unwrap();
+                    let transform_before_edit = *transform;
 
                     // --- Position ---
-                    ui.horizontal(|ui| {
+                    let position_response = ui.horizontal(|ui| {
                         ui.label("Position:");
                         ui.add(
                             egui::DragValue::new(&mut transform.translation.x)
                                 .prefix("X: ")
                                 .speed(0.1),
-                        );
-                        ui.add(
+                        ) | ui.add(
                             egui::DragValue::new(&mut transform.translation.y)
                                 .prefix("Y: ")
                                 .speed(0.1),
-                        );
-                        ui.add(
+                        ) | ui.add(
                             egui::DragValue::new(&mut transform.translation.z)
                                 .prefix("Z: ")
                                 .speed(0.1),
-                        );
+                        )
                     });
 
                     // --- Rotation ---
                     // Convert quaternion to Euler angles for editing
-                    ui.horizontal(|ui| {
+                    let rotation_response = ui.horizontal(|ui| {
                         ui.label("Rotation:");
                         ui.add(
                             egui::DragValue::new(&mut delta_pitch)
                                 .prefix("Pitch: ")
                                 .speed(1.0),
-                        );
-                        ui.add(
+                        ) | ui.add(
                             egui::DragValue::new(&mut delta_yaw)
                                 .prefix("Yaw: ")
                                 .speed(1.0),
-                        );
-                        ui.add(
+                        ) | ui.add(
                             egui::DragValue::new(&mut delta_roll)
                                 .prefix("Roll: ")
                                 .speed(1.0),
-                        );
+                        )
                     });
                     apply_rotation(
                         delta_pitch,
@@ -188,24 +457,34 @@ fn hierarchy_ui(
                     );
 
                     // --- Scale ---
-                    ui.horizontal(|ui| {
+                    let scale_response = ui.horizontal(|ui| {
                         ui.label("Scale:");
                         ui.add(
                             egui::DragValue::new(&mut transform.scale.x)
                                 .prefix("X: ")
                                 .speed(0.01),
-                        );
-                        ui.add(
+                        ) | ui.add(
                             egui::DragValue::new(&mut transform.scale.y)
                                 .prefix("Y: ")
                                 .speed(0.01),
-                        );
-                        ui.add(
+                        ) | ui.add(
                             egui::DragValue::new(&mut transform.scale.z)
                                 .prefix("Z: ")
                                 .speed(0.01),
-                        );
+                        )
                     });
+                    record_inspector_edit(
+                        &mut history,
+                        &mut inspector_edit_start.transform,
+                        transform_before_edit,
+                        *transform,
+                        position_response.inner | rotation_response.inner | scale_response.inner,
+                        |before, after| EditorEdit::Transform {
+                            entity: selected,
+                            before,
+                            after,
+                        },
+                    );
                 }
             }
 
@@ -232,6 +511,23 @@ fn hierarchy_ui(
                         n.unwrap_or(&Name::new("no name")).as_str() == new_parent_name
                     }) {
                         if new_parent != selected {
+                            if let Ok(transform) = all_transforms.get(selected) {
+                                let old_parent = query
+                                    .get(selected)
+                                    .ok()
+                                    .and_then(|(_, _, _, eco)| eco.map(|eco| eco.0));
+                                history.push(EditorEdit::Reparent {
+                                    entity: selected,
+                                    before: HierarchyState {
+                                        parent: old_parent,
+                                        transform: *transform,
+                                    },
+                                    after: HierarchyState {
+                                        parent: Some(new_parent),
+                                        transform: *transform,
+                                    },
+                                });
+                            }
                             commands
                                 .entity(selected)
                                 .insert((EditorChildOf(new_parent), ChildOf(new_parent)));
@@ -263,7 +559,9 @@ fn draw_node(
     commands: &mut Commands,
     editing_name: &mut Option<Entity>,
     ui_buffers: &mut UiBuffers,
+    history: &mut EditorHistory,
     input: &egui::InputState,
+    sub_level_q: &Query<&mut SubLevelRoot>,
 ) {
     let id = Id::new(("hierarchy_node", entity));
     let frame = Frame::default().inner_margin(4.0);
@@ -272,16 +570,20 @@ fn draw_node(
     // The payload type is Entity.
     let (_response, dropped_payload) = ui.dnd_drop_zone::<Entity, ()>(frame, |ui| {
         // draw UI, interact, etc.
-        let is_selected = selected_entity.0 == Some(entity);
+        let is_selected = selected_entity.contains(entity);
         // Handle renaming
         if *editing_name == Some(entity) {
             let rename_buffer = &mut ui_buffers.rename_buf;
             let text_response = ui.text_edit_singleline(rename_buffer);
             // Commit name on Enter or unfocus
             if text_response.lost_focus() && input.key_pressed(egui::Key::Enter) {
-                commands
-                    .entity(entity)
-                    .insert(Name::new(rename_buffer.clone()));
+                let new_name = Name::new(rename_buffer.clone());
+                history.push(EditorEdit::Rename {
+                    entity,
+                    before: query.get(entity).ok().and_then(|(_, n, _, _)| n.cloned()),
+                    after: Some(new_name.clone()),
+                });
+                commands.entity(entity).insert(new_name);
                 rename_buffer.clear();
                 *editing_name = None;
             }
@@ -294,15 +596,19 @@ fn draw_node(
 
             // Allow clicking the text edit to select as well
             if text_response.clicked() {
-                selected_entity.0 = Some(entity);
+                selected_entity.select_only(entity);
             }
         } else {
             // Draw selectable label
             let label_response = ui.selectable_label(is_selected, name);
 
-            // Single click → select
+            // Single click → select, shift-click → add/remove from the selection
             if label_response.clicked() {
-                selected_entity.0 = Some(entity);
+                if input.modifiers.shift {
+                    selected_entity.toggle(entity);
+                } else {
+                    selected_entity.select_only(entity);
+                }
             }
 
             // Double click → start renaming
@@ -320,12 +626,32 @@ fn draw_node(
     if let Some(dragged_entity) = dropped_payload {
         // Prevent parenting to self or children
         if dragged_entity != entity.into() && !would_create_cycle(&query, *dragged_entity, entity) {
+            let before = all_transforms
+                .get(*dragged_entity)
+                .ok()
+                .map(|transform| HierarchyState {
+                    parent: query
+                        .get(*dragged_entity)
+                        .ok()
+                        .and_then(|(_, _, _, eco)| eco.map(|eco| eco.0)),
+                    transform: *transform,
+                });
             set_new_relative_transform(
                 all_transforms,
                 all_global_transforms,
                 entity,
                 *dragged_entity,
             );
+            if let (Some(before), Ok(transform)) = (before, all_transforms.get(*dragged_entity)) {
+                history.push(EditorEdit::Reparent {
+                    entity: *dragged_entity,
+                    before,
+                    after: HierarchyState {
+                        parent: Some(entity),
+                        transform: *transform,
+                    },
+                });
+            }
 
             commands
                 .entity(*dragged_entity)
@@ -335,7 +661,7 @@ fn draw_node(
 
     // Draw children recursively
     if let Some(children) = children {
-        ui.indent("child_indent", |ui| {
+        let draw_children = |ui: &mut Ui| {
             for child in children.iter() {
                 if let Ok((e, n, c, _)) = query.get(child) {
                     let name_str = n.map_or("no name", |n| n.as_str());
@@ -351,11 +677,25 @@ fn draw_node(
                         commands,
                         editing_name,
                         ui_buffers,
+                        history,
                         input,
+                        sub_level_q,
                     );
                 }
             }
-        });
+        };
+        match sub_level_q.get(entity) {
+            // Sub-levels can hold a big part of the map, so they can be collapsed
+            Ok(sub_level) => {
+                egui::CollapsingHeader::new(format!("Sub-level {}", sub_level.path))
+                    .id_salt(("sub_level", entity))
+                    .default_open(true)
+                    .show(ui, draw_children);
+            }
+            Err(_) => {
+                ui.indent("child_indent", draw_children);
+            }
+        }
     }
 }
 
@@ -404,6 +744,25 @@ fn set_new_relative_transform(
         }
     }
 }
+// One history entry per drag, or per click or typed value, rather than one per frame
+fn record_inspector_edit<T: Copy + PartialEq>(
+    history: &mut EditorHistory,
+    edit_start: &mut Option<T>,
+    before: T,
+    after: T,
+    response: egui::Response,
+    edit: impl FnOnce(T, T) -> EditorEdit,
+) {
+    if response.drag_started() {
+        *edit_start = Some(before);
+    }
+    if response.drag_stopped() || (response.changed() && edit_start.is_none()) {
+        let start = edit_start.take().unwrap_or(before);
+        if start != after {
+            history.push(edit(start, after));
+        }
+    }
+}
 fn apply_rotation(
     new_pitch: f32,
     new_yaw: f32,
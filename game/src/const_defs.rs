@@ -3,3 +3,12 @@ pub const PLAYER_COLLIDER_HEIGHT: f32 = 1.0;
 pub const GROUND_TIMER: f32 = 0.1;
 pub const JUMP_SPEED: f32 = 0.04;
 pub const GRAVITY: f32 = -0.01;
+// How close the player has to be to use a door, lever or terminal
+pub const INTERACT_DISTANCE: f32 = 2.5;
+pub const DOOR_OPEN_ANGLE: f32 = std::f32::consts::FRAC_PI_2;
+pub const LEVER_PULL_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+// How quickly doors and levers turn toward their open or closed rotation
+pub const INTERACTABLE_TURN_SPEED: f32 = 8.0;
+// How much further than its load distance the player has to go before a sub-level unloads,
+// so walking along the edge doesn't load and unload it every frame
+pub const SUB_LEVEL_UNLOAD_MARGIN: f32 = 10.0;
@@ -15,6 +15,7 @@ pub struct PlayerInput {
     pub running: bool,
     pub sneaking: bool,
     pub requested_jump: bool,
+    pub requested_interact: bool,
 }
 #[derive(Default, Resource)]
 pub struct GameAssets {
@@ -25,3 +26,17 @@ pub struct LevelSpawner(pub Handle<Scene>);
 
 #[derive(Component)]
 pub struct AwaitingTransformPropagation;
+// Gameplay components attached to objects of levels made in the editor
+#[derive(Component, Debug)]
+pub struct Interactable(pub common::Interactable);
+// Where the player spawns, the object with the Player component in the level
+#[derive(Component)]
+pub struct PlayerStart;
+// Object another level file is loaded below, see streaming.rs
+#[derive(Component)]
+pub struct SubLevel {
+    // Asset path of the level file
+    pub path: String,
+    // Loaded within this distance of the player, None keeps it loaded
+    pub load_distance: Option<f32>,
+}
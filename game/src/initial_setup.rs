@@ -99,6 +99,47 @@ pub fn setup_scene(
         Transform::from_rotation(Quat::from_euler(EulerRot::ZYX, 0.0, 0.0, -FRAC_PI_4)),
     ));
 
+    let mut forward_mat: StandardMaterial = Color::srgb(0.1, 0.2, 0.1).into();
+    forward_mat.opaque_render_method = OpaqueRendererMethod::Forward;
+
+    // Plane
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(50.0, 50.0))),
+        MeshMaterial3d(materials.add(forward_mat)),
+        Collider::cuboid(50.0 / 2.0, 0.0 / 2.0, 50.0 / 2.0),
+    ));
+    // sky
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(2.0, 1.0, 1.0))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Srgba::hex("888888").unwrap().into(),
+            unlit: true,
+            cull_mode: None,
+            ..default()
+        })),
+        Transform::from_scale(Vec3::splat(1_000_000.0)),
+        NotShadowCaster,
+        NotShadowReceiver,
+    ));
+
+    // Example instructions
+    commands.spawn((
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+    ));
+}
+/// The test content shown when no level file is passed on the command line
+pub fn spawn_demo_scene(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
     // FlightHelmet
     let helmet_scene = asset_server
         .load(GltfAssetLabel::Scene(0).from_asset("models/FlightHelmet/FlightHelmet.gltf"));
@@ -119,12 +160,6 @@ pub fn setup_scene(
     forward_mat.opaque_render_method = OpaqueRendererMethod::Forward;
     let forward_mat_h = materials.add(forward_mat);
 
-    // Plane
-    commands.spawn((
-        Mesh3d(meshes.add(Plane3d::default().mesh().size(50.0, 50.0))),
-        MeshMaterial3d(forward_mat_h.clone()),
-        Collider::cuboid(50.0 / 2.0, 0.0 / 2.0, 50.0 / 2.0),
-    ));
     // The normal map. Note that to generate it in the GIMP image editor, you should
     // open the depth map, and do Filters → Generic → Normal Map
     // You should enable the "flip X" checkbox.
@@ -234,31 +269,6 @@ pub fn setup_scene(
             ),
         ));
     }
-
-    // sky
-    commands.spawn((
-        Mesh3d(meshes.add(Cuboid::new(2.0, 1.0, 1.0))),
-        MeshMaterial3d(materials.add(StandardMaterial {
-            base_color: Srgba::hex("888888").unwrap().into(),
-            unlit: true,
-            cull_mode: None,
-            ..default()
-        })),
-        Transform::from_scale(Vec3::splat(1_000_000.0)),
-        NotShadowCaster,
-        NotShadowReceiver,
-    ));
-
-    // Example instructions
-    commands.spawn((
-        Text::default(),
-        Node {
-            position_type: PositionType::Absolute,
-            top: Val::Px(12.0),
-            left: Val::Px(12.0),
-            ..default()
-        },
-    ));
 }
 pub fn detect_gltf_children(
     trigger: Trigger<SceneInstanceReady>,
@@ -56,4 +56,7 @@ fn handle_keyboard_input(
     if !player_input.requested_jump {
         player_input.requested_jump = keyboard_input.just_pressed(KeyCode::Space);
     }
+    if keyboard_input.just_pressed(KeyCode::KeyE) {
+        player_input.requested_interact = true;
+    }
 }
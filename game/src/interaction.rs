@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use common::InteractableType;
+
+use crate::{
+    camera::PlayerCameraTarget,
+    const_defs::{DOOR_OPEN_ANGLE, INTERACT_DISTANCE, INTERACTABLE_TURN_SPEED, LEVER_PULL_ANGLE},
+    defs::{Interactable, PlayerInput},
+};
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (setup_interactables, interact, animate_interactables).chain(),
+        )
+        .add_systems(EguiContextPass, terminal_ui);
+    }
+}
+
+// Whether a door is open, a lever pulled or a terminal in use
+#[derive(Component)]
+pub struct InteractableState {
+    pub active: bool,
+    // Rotation from the level, doors and levers turn away from it while active
+    rest_rotation: Quat,
+}
+
+fn setup_interactables(
+    interactable_q: Query<(Entity, &Transform), Added<Interactable>>,
+    mut commands: Commands,
+) {
+    for (entity, transform) in &interactable_q {
+        commands.entity(entity).insert(InteractableState {
+            active: false,
+            rest_rotation: transform.rotation,
+        });
+    }
+}
+// The interact key toggles the closest interactable within reach
+fn interact(
+    mut player_input: ResMut<PlayerInput>,
+    player_q: Query<&GlobalTransform, With<PlayerCameraTarget>>,
+    mut interactable_q: Query<(&GlobalTransform, &mut InteractableState)>,
+) {
+    if !player_input.requested_interact {
+        return;
+    }
+    player_input.requested_interact = false;
+    let Ok(player_transform) = player_q.single() else {
+        return;
+    };
+    let closest = interactable_q
+        .iter_mut()
+        .map(|(transform, state)| {
+            (
+                transform
+                    .translation()
+                    .distance(player_transform.translation()),
+                state,
+            )
+        })
+        .filter(|(distance, _)| *distance <= INTERACT_DISTANCE)
+        .min_by(|(distance, _), (other_distance, _)| distance.total_cmp(other_distance));
+    if let Some((_, mut state)) = closest {
+        state.active = !state.active;
+    }
+}
+// Doors swing open around their up axis, levers tilt around their side axis
+fn animate_interactables(
+    time: Res<Time>,
+    mut interactable_q: Query<(&Interactable, &InteractableState, &mut Transform)>,
+) {
+    for (interactable, state, mut transform) in &mut interactable_q {
+        let target = match (interactable.0.kind, state.active) {
+            (_, false) | (InteractableType::Terminal, true) => state.rest_rotation,
+            (InteractableType::Door, true) => {
+                state.rest_rotation * Quat::from_rotation_y(DOOR_OPEN_ANGLE)
+            }
+            (InteractableType::Lever, true) => {
+                state.rest_rotation * Quat::from_rotation_x(LEVER_PULL_ANGLE)
+            }
+        };
+        if transform.rotation != target {
+            transform.rotation = transform.rotation.slerp(
+                target,
+                (INTERACTABLE_TURN_SPEED * time.delta_secs()).min(1.0),
+            );
+        }
+    }
+}
+// The cursor stays locked to the camera, so terminals are closed with the interact key too
+fn terminal_ui(
+    mut egui_ctx: EguiContexts,
+    interactable_q: Query<(Entity, &Interactable, &InteractableState, Option<&Name>)>,
+) {
+    let Some(ctx) = egui_ctx.try_ctx_mut() else {
+        return;
+    };
+    for (entity, interactable, state, name) in &interactable_q {
+        if interactable.0.kind != InteractableType::Terminal || !state.active {
+            continue;
+        }
+        egui::Window::new(name.map_or("Terminal", |name| name.as_str()))
+            .id(egui::Id::new(("terminal", entity)))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("> ready_");
+                ui.separator();
+                ui.label("Press E to log off");
+            });
+    }
+}
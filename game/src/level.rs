@@ -1 +1,292 @@
-pub fn load_level(level_ron_path: &str) {}
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use bevy::{
+    asset::{AssetPath, UntypedAssetId, io::file::FileAssetReader},
+    gltf::{GltfMesh, GltfNode},
+    prelude::*,
+};
+use common::{
+    asset_roots::AssetRoots,
+    level::{AssetLabel, AssetRef, LevelComponent, LevelFile, LevelTransform},
+};
+
+use crate::{
+    camera::PlayerCameraTarget,
+    const_defs::PLAYER_COLLIDER_HEIGHT,
+    defs::{Interactable, PlayerStart, SubLevel},
+};
+
+pub struct LevelLoaderPlugin;
+
+// The level file passed on the command line, e.g. `game assets/levels/first.ron`
+#[derive(Resource)]
+pub struct LevelToLoad(pub PathBuf);
+// Folders the prefab and sub-level paths in levels are relative to
+#[derive(Resource, Deref)]
+pub struct LevelAssetRoots(pub AssetRoots);
+// A glTF node or mesh a level object places, spawned as a scene once it has loaded
+#[derive(Component)]
+enum GltfPart {
+    Node(Handle<GltfNode>),
+    Mesh(Handle<GltfMesh>),
+}
+
+impl Plugin for LevelLoaderPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(path) = std::env::args().nth(1) {
+            app.insert_resource(LevelToLoad(PathBuf::from(path)));
+        }
+        app.insert_resource(LevelAssetRoots(AssetRoots::load(
+            &FileAssetReader::get_base_path(),
+        )))
+        .add_systems(Startup, load_level.run_if(resource_exists::<LevelToLoad>))
+        .add_systems(Update, build_gltf_part_scenes)
+        .add_systems(
+            PostUpdate,
+            move_player_to_start.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+fn load_level(
+    level_to_load: Res<LevelToLoad>,
+    asset_roots: Res<LevelAssetRoots>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let path = &level_to_load.0;
+    let level_ron = match fs::read_to_string(path) {
+        Ok(level_ron) => level_ron,
+        Err(e) => {
+            eprintln!("Error reading level {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let level = match LevelFile::from_ron(&level_ron, &asset_roots) {
+        Ok(level) => level,
+        Err(e) => {
+            eprintln!("Error loading level {}: {}", path.display(), e);
+            return;
+        }
+    };
+    // The game doesn't need to know which objects came from prefabs
+    let level =
+        level.instantiate_prefabs(&mut |prefab_path| read_level_asset(&asset_roots, prefab_path));
+    spawn_level(&mut commands, &asset_server, &level, None);
+}
+/// Read a prefab or sub-level file by its asset path, printing what went wrong if it can't be
+pub fn read_level_asset(asset_roots: &AssetRoots, asset_path: &str) -> Option<LevelFile> {
+    let level = asset_roots
+        .file_path(asset_path)
+        .ok_or_else(|| "unknown asset root".to_owned())
+        .and_then(|file_path| fs::read_to_string(file_path).map_err(|e| e.to_string()))
+        .and_then(|level_ron| {
+            LevelFile::from_ron(&level_ron, asset_roots).map_err(|e| e.to_string())
+        });
+    match level {
+        Ok(level) => Some(level),
+        Err(e) => {
+            eprintln!("Error loading {}: {}", asset_path, e);
+            None
+        }
+    }
+}
+/// Spawn every object of a level with its glTF scene, hierarchy and gameplay components,
+/// with the top level objects below `parent`. Returns the top level objects
+pub fn spawn_level(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    level: &LevelFile,
+    parent: Option<Entity>,
+) -> Vec<Entity> {
+    let mut spawned: HashMap<u64, Entity> = HashMap::new();
+    let mut top_level = Vec::new();
+    for level_entity in level.sorted_by_hierarchy() {
+        let name = level_entity
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Object {}", level_entity.id));
+        let mut entity_commands = commands.spawn((
+            Name::new(name),
+            transform_from_level(&level_entity.transform),
+            Visibility::default(),
+        ));
+        if let Some(asset) = &level_entity.asset {
+            insert_asset(&mut entity_commands, asset_server, asset);
+        }
+        for component in &level_entity.components {
+            match component {
+                LevelComponent::Interactable(interactable) => {
+                    entity_commands.insert(Interactable(interactable.clone()));
+                }
+                LevelComponent::Player(_) => {
+                    entity_commands.insert(PlayerStart);
+                }
+            }
+        }
+        // The streaming plugin loads the sub-level's contents below it
+        if let Some(sub_level) = &level_entity.sub_level {
+            entity_commands.insert(SubLevel {
+                path: sub_level.path.clone(),
+                load_distance: sub_level.load_distance,
+            });
+        }
+        match level_entity
+            .parent
+            .and_then(|parent| spawned.get(&parent).copied())
+        {
+            Some(level_parent) => {
+                entity_commands.insert(ChildOf(level_parent));
+            }
+            None => {
+                if let Some(parent) = parent {
+                    entity_commands.insert(ChildOf(parent));
+                }
+                top_level.push(entity_commands.id());
+            }
+        }
+        spawned.insert(level_entity.id, entity_commands.id());
+    }
+    top_level
+}
+// The label picks a scene, node or mesh of the glTF, like "Scene1", "Node3" or "Mesh0"
+fn insert_asset(
+    entity_commands: &mut EntityCommands,
+    asset_server: &AssetServer,
+    asset: &AssetRef,
+) {
+    let label = asset.label.as_deref().unwrap_or("Scene0");
+    let path = AssetPath::from(asset.path.clone()).with_label(label.to_owned());
+    match AssetLabel::parse(label) {
+        Some(AssetLabel::Node(_)) => {
+            entity_commands.insert(GltfPart::Node(asset_server.load(path)));
+        }
+        Some(AssetLabel::Mesh(_)) => {
+            entity_commands.insert(GltfPart::Mesh(asset_server.load(path)));
+        }
+        Some(AssetLabel::Scene(_)) | None => {
+            entity_commands.insert(SceneRoot(asset_server.load(path)));
+        }
+    }
+}
+// Build the scene of each node or mesh once its glTF has loaded, shared by every object
+// placing the same one.
+// Copied with spawn_gltf_node and spawn_gltf_mesh from editor/src/gltf_parts.rs, which also
+// rebuilds them when a glTF is reloaded. The game is on another bevy version than the editor
+// and common, so they can't be shared, fix both
+fn build_gltf_part_scenes(
+    parts_q: Query<(Entity, &GltfPart), Without<SceneRoot>>,
+    gltf_nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    asset_server: Res<AssetServer>,
+    mut scenes: ResMut<Assets<Scene>>,
+    mut built: Local<HashMap<UntypedAssetId, Handle<Scene>>>,
+    mut commands: Commands,
+) {
+    for (entity, part) in &parts_q {
+        let id = match part {
+            GltfPart::Node(node) => node.id().untyped(),
+            GltfPart::Mesh(mesh) => mesh.id().untyped(),
+        };
+        if asset_server.load_state(id).is_failed() {
+            commands.entity(entity).remove::<GltfPart>();
+            continue;
+        }
+        if let Some(scene) = built.get(&id) {
+            commands.entity(entity).insert(SceneRoot(scene.clone()));
+            continue;
+        }
+        let mut world = World::new();
+        match part {
+            GltfPart::Node(node) => {
+                let Some(node) = gltf_nodes.get(node) else {
+                    continue;
+                };
+                // The level object takes the place of the node's translation
+                let transform = Transform {
+                    translation: Vec3::ZERO,
+                    ..node.transform
+                };
+                spawn_gltf_node(&mut world, node, transform, &gltf_nodes, &gltf_meshes);
+            }
+            GltfPart::Mesh(mesh) => {
+                let Some(mesh) = gltf_meshes.get(mesh) else {
+                    continue;
+                };
+                let root = world
+                    .spawn((Transform::default(), Visibility::default()))
+                    .id();
+                spawn_gltf_mesh(&mut world, mesh, root);
+            }
+        }
+        let scene = scenes.add(Scene::new(world));
+        built.insert(id, scene.clone());
+        commands.entity(entity).insert(SceneRoot(scene));
+    }
+}
+fn spawn_gltf_node(
+    world: &mut World,
+    node: &GltfNode,
+    transform: Transform,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+) -> Entity {
+    let entity = world
+        .spawn((
+            Name::new(node.name.clone()),
+            transform,
+            Visibility::default(),
+        ))
+        .id();
+    if let Some(mesh) = node.mesh.as_ref().and_then(|mesh| gltf_meshes.get(mesh)) {
+        spawn_gltf_mesh(world, mesh, entity);
+    }
+    for child in node
+        .children
+        .iter()
+        .filter_map(|child| gltf_nodes.get(child))
+    {
+        let child_entity = spawn_gltf_node(world, child, child.transform, gltf_nodes, gltf_meshes);
+        world.entity_mut(child_entity).insert(ChildOf(entity));
+    }
+    entity
+}
+// One entity per primitive, like the scenes the glTF loader spawns
+fn spawn_gltf_mesh(world: &mut World, mesh: &GltfMesh, parent: Entity) {
+    for primitive in &mesh.primitives {
+        let mut primitive_entity = world.spawn((
+            Name::new(primitive.name.clone()),
+            Mesh3d(primitive.mesh.clone()),
+            Transform::default(),
+            Visibility::default(),
+            ChildOf(parent),
+        ));
+        if let Some(material) = &primitive.material {
+            primitive_entity.insert(MeshMaterial3d(material.clone()));
+        }
+    }
+}
+fn transform_from_level(transform: &LevelTransform) -> Transform {
+    Transform {
+        translation: Vec3::from_array(transform.translation),
+        rotation: Quat::from_array(transform.rotation),
+        scale: Vec3::from_array(transform.scale),
+    }
+}
+// Runs once the start's GlobalTransform is known, the start marks the player's feet.
+// Only the first start counts, streamed sub-levels may bring their own
+fn move_player_to_start(
+    start_q: Query<&GlobalTransform, Added<PlayerStart>>,
+    mut player_q: Query<&mut Transform, With<PlayerCameraTarget>>,
+    mut moved_to_start: Local<bool>,
+) {
+    if *moved_to_start {
+        return;
+    }
+    let Some(start) = start_q.iter().next() else {
+        return;
+    };
+    *moved_to_start = true;
+    for mut player_transform in &mut player_q {
+        player_transform.translation = start.translation() + Vec3::Y * PLAYER_COLLIDER_HEIGHT;
+    }
+}
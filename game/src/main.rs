@@ -1,6 +1,7 @@
 use std::f32::consts::*;
 
 use bevy::{
+    asset::io::{AssetSourceBuilder, file::FileAssetReader},
     core_pipeline::prepass::{DeferredPrepass, DepthPrepass, MotionVectorPrepass, NormalPrepass},
     image::ImageLoaderSettings,
     math::ops,
@@ -13,24 +14,41 @@ mod defs;
 mod events;
 mod initial_setup;
 mod input;
+mod interaction;
 mod level;
 mod movement;
 mod sound;
+mod streaming;
 use bevy_egui::EguiPlugin;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_kira_audio::{AudioPlugin, SpatialAudioPlugin};
 use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin, TimestepMode};
 use camera::PlayerCameraPlugin;
+use common::asset_roots::AssetRoots;
 use defs::GameAssets;
-use initial_setup::{detect_gltf_children, handle_level_spawning, setup_scene, spawn_player};
+use initial_setup::{
+    detect_gltf_children, handle_level_spawning, setup_scene, spawn_demo_scene, spawn_player,
+};
 use input::GameInputPlugin;
+use interaction::InteractionPlugin;
+use level::{LevelLoaderPlugin, LevelToLoad};
 use movement::player_movement;
 use sound::generate_sound;
+use streaming::LevelStreamingPlugin;
 
 use crate::sound::cleanup_finished_audio;
 fn main() {
-    App::new()
-        .insert_resource(DefaultOpaqueRendererMethod::deferred())
+    let mut app = App::new();
+    // Extra asset roots have to be registered as asset sources before the AssetPlugin
+    let asset_roots = AssetRoots::load(&FileAssetReader::get_base_path());
+    for root in &asset_roots.extra_roots {
+        let root_path = asset_roots.root_path(root);
+        app.register_asset_source(
+            root.name.clone(),
+            AssetSourceBuilder::platform_default(&root_path.to_string_lossy(), None),
+        );
+    }
+    app.insert_resource(DefaultOpaqueRendererMethod::deferred())
         .insert_resource(DirectionalLightShadowMap { size: 4096 })
         .insert_resource(TimestepMode::Fixed {
             dt: 1.0 / 64.0,
@@ -50,10 +68,17 @@ fn main() {
                 enable_multipass_for_primary_context: true,
             },
             WorldInspectorPlugin::default(),
+            LevelLoaderPlugin,
+            LevelStreamingPlugin,
+            InteractionPlugin,
         ))
         .insert_resource(Pause(true))
         .insert_resource(GameAssets::default())
         .add_systems(Startup, setup_scene)
+        .add_systems(
+            Startup,
+            spawn_demo_scene.run_if(not(resource_exists::<LevelToLoad>)),
+        )
         .add_systems(Startup, (spawn_player).after(setup_scene))
         .add_systems(Update, (animate_light_direction, switch_mode, spin))
         .add_systems(Update, player_movement)
@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::PlayerCameraTarget,
+    const_defs::SUB_LEVEL_UNLOAD_MARGIN,
+    defs::SubLevel,
+    level::{LevelAssetRoots, read_level_asset, spawn_level},
+};
+
+pub struct LevelStreamingPlugin;
+
+impl Plugin for LevelStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        // Distances use GlobalTransform, so run once it's up to date for this frame
+        app.add_systems(
+            PostUpdate,
+            stream_sub_levels.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+// The top level objects of a sub-level whose contents are spawned
+#[derive(Component)]
+struct LoadedSubLevel(Vec<Entity>);
+
+// Load sub-levels the player comes close to and unload the ones left behind.
+// Sub-levels without a load distance load once and stay
+fn stream_sub_levels(
+    sub_level_q: Query<(Entity, &SubLevel, &GlobalTransform, Option<&LoadedSubLevel>)>,
+    player_q: Query<&GlobalTransform, With<PlayerCameraTarget>>,
+    parent_q: Query<&ChildOf>,
+    asset_roots: Res<LevelAssetRoots>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let player_position = player_q.iter().next().map(GlobalTransform::translation);
+    for (entity, sub_level, transform, loaded) in &sub_level_q {
+        let distance = player_position.map(|position| position.distance(transform.translation()));
+        let in_range = |margin: f32| match (sub_level.load_distance, distance) {
+            (None, _) => true,
+            (Some(load_distance), Some(distance)) => distance <= load_distance + margin,
+            (Some(_), None) => false,
+        };
+        match loaded {
+            Some(loaded) => {
+                if !in_range(SUB_LEVEL_UNLOAD_MARGIN) {
+                    for &object in &loaded.0 {
+                        commands.entity(object).try_despawn();
+                    }
+                    commands.entity(entity).remove::<LoadedSubLevel>();
+                }
+            }
+            None => {
+                if !in_range(0.0) {
+                    continue;
+                }
+                // Marked even when loading fails so the error isn't printed every frame
+                let objects = load_sub_level(
+                    entity,
+                    sub_level,
+                    &sub_level_q,
+                    &parent_q,
+                    &asset_roots,
+                    &asset_server,
+                    &mut commands,
+                );
+                commands.entity(entity).insert(LoadedSubLevel(objects));
+            }
+        }
+    }
+}
+fn load_sub_level(
+    entity: Entity,
+    sub_level: &SubLevel,
+    sub_level_q: &Query<(Entity, &SubLevel, &GlobalTransform, Option<&LoadedSubLevel>)>,
+    parent_q: &Query<&ChildOf>,
+    asset_roots: &LevelAssetRoots,
+    asset_server: &AssetServer,
+    commands: &mut Commands,
+) -> Vec<Entity> {
+    // A sub-level placed inside itself would keep loading forever
+    let nested_in_itself = parent_q.iter_ancestors(entity).any(|ancestor| {
+        sub_level_q
+            .get(ancestor)
+            .is_ok_and(|(_, ancestor_sub_level, _, _)| ancestor_sub_level.path == sub_level.path)
+    });
+    if nested_in_itself {
+        eprintln!(
+            "Error loading sub-level {}: it's placed inside itself",
+            sub_level.path
+        );
+        return Vec::new();
+    }
+    let Some(level) = read_level_asset(asset_roots, &sub_level.path) else {
+        return Vec::new();
+    };
+    let level =
+        level.instantiate_prefabs(&mut |prefab_path| read_level_asset(asset_roots, prefab_path));
+    spawn_level(commands, asset_server, &level, Some(entity))
+}